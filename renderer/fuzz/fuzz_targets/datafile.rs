@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use renderer::{DataFile, Interpreter, NullSink};
+
+fuzz_target!(|data: &[u8]| {
+    // whatever parses has to make it through the interpreter without panicking, frames included
+    if let Ok(data) = serde_json::from_slice::<DataFile>(data) {
+        let _ = Interpreter::new(data.clone()).snapshots();
+        Interpreter::new(data).render(NullSink);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use renderer::{instr::Fen, Chessboard};
+
+fuzz_target!(|fen: &str| {
+    for ([f, r], _) in Fen::new(fen.to_owned()).iter() {
+        assert!((1..=8).contains(&f) && (1..=8).contains(&r), "{fen:?}");
+    }
+    if let Ok(fen) = Fen::parse(fen) {
+        assert_eq!(fen.iter().count(), 64);
+        Chessboard::new().set_fen(&fen);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use renderer::instr::line_as_can2coord;
+
+fuzz_target!(|line: &str| {
+    if let Some((squares, _)) = line_as_can2coord(line) {
+        for [f, r] in squares {
+            assert!((1..=8).contains(&f) && (1..=8).contains(&r), "{line:?}");
+        }
+    }
+});
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use crate::{
+    instr::{Color, MoveData},
+    Chessboard, FrameSink, Game, GifSink, Interpreter,
+};
+#[cfg(not(disable_ffmpeg))]
+use crate::{
+    video::{ffmpeg_animate, preflight},
+    DirSink,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+    Webp,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Some(
+            match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+                "gif" => Self::Gif,
+                "apng" | "png" => Self::Apng,
+                "webp" => Self::Webp,
+                _ => return None,
+            },
+        )
+    }
+
+    // what ffmpeg encodes it with, GIFs are encoded in-process
+    pub fn encoder(self) -> Option<&'static str> {
+        match self {
+            Self::Gif => None,
+            Self::Apng => Some("apng"),
+            Self::Webp => Some("libwebp_anim"),
+        }
+    }
+
+    // ffmpeg output arguments, GIFs are encoded in-process
+    #[rustfmt::skip]
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            Self::Gif => &[],
+            Self::Apng => &["-f", "apng", "-plays", "0"],
+            Self::Webp => &["-c:v", "libwebp_anim", "-lossless", "1", "-loop", "0"],
+        }
+    }
+}
+
+// positions along the line ending at `id` (the main line when `None`), the starting one
+// included, each with the move leading to it tinted and with its arrow unless `arrows` is off
+pub fn line(game: &Game, id: Option<usize>, arrows: bool) -> Vec<Chessboard> {
+    let line = id.map_or_else(|| game.main_line(), |id| game.line(id));
+    let mut board = Chessboard::new();
+    board.set_fen(&game.init);
+    let mut boards = vec![];
+    // a root holding a FEN is the starting position already
+    if !matches!(
+        line.first().map(|id| &game.moves[id].data),
+        Some(MoveData::Fen(_))
+    ) {
+        boards.push(board.clone());
+    }
+    for id in line {
+        Interpreter::mov(&mut board, game, id);
+        if let Some(last_move) = board.last_move().filter(|_| arrows) {
+            board.arrow(last_move, Color::Blue);
+        }
+        boards.push(board.clone());
+    }
+    boards
+}
+
+// every board for `delay` seconds, the last one twice as long so the loop doesn't rush back
+pub fn render<S: FrameSink>(boards: &[Chessboard], delay: f64, mut sink: S) -> S::Output {
+    for (i, board) in boards.iter().enumerate() {
+        let hold = if i + 1 == boards.len() { 2.0 } else { 1.0 };
+        sink.push_frame(board.render(), i as f64 * delay, delay * hold);
+    }
+    sink.finish()
+}
+
+pub fn export(boards: &[Chessboard], delay: f64, out: &Path) -> Result<(), String> {
+    let format = Format::from_path(out)
+        .ok_or_else(|| format!("{}: expected a .gif, .apng or .webp", out.display()))?;
+    if boards.is_empty() {
+        return Err("nothing to animate".to_owned());
+    }
+    match format {
+        Format::Gif => {
+            render(boards, delay, GifSink::new(out));
+            Ok(())
+        }
+        #[cfg(not(disable_ffmpeg))]
+        _ => {
+            preflight()?.require_encoders(format.encoder().as_slice())?;
+            let tmp = std::env::temp_dir().join(format!("renderer-anim-{}", std::process::id()));
+            std::fs::create_dir_all(&tmp).map_err(|e| e.to_string())?;
+            let concat = render(boards, delay, DirSink::new(&tmp));
+            let code = ffmpeg_animate(&concat, out, format.args());
+            std::fs::remove_dir_all(&tmp).unwrap_or(());
+            match code {
+                0 => Ok(()),
+                code => Err(format!("ffmpeg exited with {code}")),
+            }
+        }
+        #[cfg(disable_ffmpeg)]
+        _ => Err(format!("{format:?} needs ffmpeg, only GIFs are built in")),
+    }
+}
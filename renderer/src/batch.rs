@@ -0,0 +1,338 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::scope,
+};
+
+use crossbeam::channel::{self, Receiver, Sender};
+use image::RgbaImage;
+use rayon::prelude::*;
+#[cfg(not(disable_ffmpeg))] use renderer::video::{ffmpeg_join, ffmpeg_remux};
+use renderer::{
+    cancel, cancelled,
+    check::{repair, Repair},
+    markers::Markers,
+    video::{clean_partials, duration, subtitle_encoder},
+    DataFile, DirSink, FrameSink, Interpreter,
+};
+
+use crate::{
+    check_tools,
+    manifest::{Manifest, Stamp},
+    progress::Progress,
+    report::{Outcome, Report},
+    site, write_player, write_subs, write_timeline,
+};
+
+// chapter markers for the video, written next to its frames
+const META: &str = "ffmetadata.txt";
+
+// what to add to the videos once they're joined
+#[derive(Clone, Copy)]
+struct Remux {
+    subs: bool,
+    chapters: bool,
+}
+
+// what to write next to the videos, from the same (repaired) data they're rendered from
+#[derive(Clone, Copy)]
+struct Extras {
+    html: bool,
+    subs: bool,
+    timeline: bool,
+}
+
+pub struct Job {
+    pub video: PathBuf,
+    pub concat: PathBuf,
+    pub out: PathBuf,
+    pub tmp: PathBuf,
+    pub t: Option<f64>,
+    pub key: String,
+    pub stamp: Stamp,
+}
+
+struct Counted<'a, S>(S, &'a Progress);
+
+impl<S: FrameSink> FrameSink for Counted<'_, S> {
+    type Output = S::Output;
+
+    fn push_frame(&mut self, frame: RgbaImage, start: f64, duration: f64) {
+        self.0.push_frame(frame, start, duration);
+        self.1.frames(1);
+    }
+
+    fn finish(self) -> S::Output { self.0.finish() }
+}
+
+pub fn prod() {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(8)
+        .build_global()
+        .unwrap();
+    let remux = Remux {
+        subs: env::args().any(|a| a == "--mux-subs"),
+        chapters: env::args().any(|a| a == "--chapters"),
+    };
+    // the videos are MP4s
+    let subs = [subtitle_encoder(Path::new(".mp4"))];
+    check_tools(if remux.subs { &subs } else { &[] });
+    handle_signals();
+    let args = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect::<Vec<_>>();
+    let quiet = env::args().any(|a| a == "--quiet");
+    let site = env::args().any(|a| a == "--site");
+    let extras = Extras {
+        html: env::args().any(|a| a == "--html"),
+        subs: remux.subs || env::args().any(|a| a == "--subs"),
+        timeline: env::args().any(|a| a == "--timeline"),
+    };
+    let out: PathBuf = args[1].clone().into();
+    clean_partials(&out);
+    let manifest = &Manifest::open(&out);
+    let chapters = fs::read_dir(&args[0])
+        .unwrap()
+        .flat_map(|course| {
+            let course = course.unwrap();
+            let out2 = out.join(course.file_name());
+            fs::create_dir(&out2).unwrap_or(());
+            fs::read_dir(course.path())
+                .unwrap()
+                .map(move |x| (x.unwrap(), out2.clone()))
+        })
+        .collect::<Vec<_>>();
+    let report = &Report::new(
+        &out,
+        Progress::new(
+            chapters
+                .iter()
+                .map(|(_, out)| out.file_name().unwrap().to_string_lossy().into_owned()),
+        ),
+    );
+    let (sx, ref rx) = channel::unbounded();
+    scope(|s| {
+        #[cfg(not(disable_ffmpeg))]
+        let workers = (0..4)
+            .map(|_| s.spawn(|| handle_ffmpeg(rx, manifest, report, remux)))
+            .collect::<Vec<_>>();
+        #[cfg(disable_ffmpeg)]
+        let workers = Vec::<std::thread::ScopedJoinHandle<()>>::new();
+        s.spawn(|| report.progress().display(|| rx.len(), quiet));
+        chapters.into_par_iter().for_each(|(chapter, out)| {
+            let file_name = chapter.file_name();
+            let name = file_name.to_string_lossy();
+            let key = format!("{}/{name}.mp4", out.file_name().unwrap().to_string_lossy());
+            if cancelled() {
+                return report.record(Outcome::Cancelled, key);
+            }
+            match catch_unwind(|| {
+                let progress = report.progress();
+                handle_chapter(
+                    chapter.path(),
+                    &name,
+                    &out,
+                    &key,
+                    extras,
+                    manifest,
+                    progress,
+                    &sx,
+                )
+            }) {
+                Ok(Some(outcome)) => report.record(outcome, key),
+                Ok(None) => {}
+                Err(e) => {
+                    BufWriter::new(File::create(out.join(format!("panic_{name}"))).unwrap())
+                        .write_all(e.downcast_ref::<&str>().unwrap_or(&"").as_bytes())
+                        .unwrap_or(());
+                    report.record(Outcome::Panicked, key);
+                }
+            }
+        });
+        drop(sx);
+        workers.into_iter().for_each(|w| w.join().unwrap_or(()));
+        // jobs no worker was there to run, all of them without ffmpeg
+        for Job { key, .. } in rx.try_iter() {
+            report.record(Outcome::Failed, key);
+        }
+        report.progress().close();
+    });
+    if cancelled() {
+        clean_tmp(&out);
+    } else if site {
+        site::write(args[0].as_ref(), &out, site::sheet_every()).unwrap_or_else(|e| {
+            eprintln!("site: {e}");
+        });
+    }
+    report.write(cancelled());
+    if cancelled() {
+        process::exit(130);
+    }
+}
+
+// `--repair`s the data when asked, what's rendered from a chapter and written alongside has to
+// go through here; otherwise the video is cut at the first thing the interpreter can't get past
+pub fn prepare(data: &mut DataFile) -> Vec<Repair> {
+    if env::args().any(|a| a == "--repair") {
+        repair(data)
+    } else {
+        vec![]
+    }
+}
+
+pub fn interpreter(data: DataFile) -> Interpreter {
+    Interpreter::new(data).two_boards(env::args().any(|a| a == "--two-boards"))
+}
+
+#[cfg(not(disable_ffmpeg))]
+fn handle_ffmpeg(rx: &Receiver<Job>, manifest: &Manifest, report: &Report, remux: Remux) {
+    while let Ok(job) = rx.recv() {
+        let (key, tmp) = (job.key.clone(), job.tmp.clone());
+        // the chapter still has to be recorded for the run to end
+        if catch_unwind(AssertUnwindSafe(|| {
+            ffmpeg_job(job, manifest, report, remux)
+        }))
+        .is_err()
+        {
+            fs::remove_dir_all(&tmp).unwrap_or(());
+            report.record(Outcome::Panicked, key);
+        }
+    }
+}
+
+#[cfg(not(disable_ffmpeg))]
+fn ffmpeg_job(
+    Job { video, concat, out, tmp, t, key, stamp }: Job,
+    manifest: &Manifest,
+    report: &Report,
+    remux: Remux,
+) {
+    let subs = Some(out.with_extension("vtt")).filter(|x| remux.subs && x.exists());
+    let meta = Some(tmp.join(META)).filter(|x| remux.chapters && x.exists());
+    if cancelled() {
+        report.record(Outcome::Cancelled, key);
+    } else if ffmpeg_join(&video, &concat, &out, t) == 0
+        && (subs.is_none() && meta.is_none()
+            || ffmpeg_remux(&out, subs.as_deref(), meta.as_deref()) == 0)
+    {
+        manifest.complete(key.clone(), stamp);
+        report.record(Outcome::Rendered, key);
+    } else if cancelled() {
+        report.record(Outcome::Cancelled, key);
+    } else {
+        report.record(Outcome::Failed, key);
+    }
+    fs::remove_dir_all(&tmp).unwrap_or(());
+}
+
+fn handle_signals() {
+    static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+    ctrlc::set_handler(|| {
+        if SIGNALS.fetch_add(1, Ordering::Relaxed) == 0 {
+            eprintln!("cancelling, waiting for in-flight chapters (signal again to quit now)");
+            cancel();
+        } else {
+            process::exit(130);
+        }
+    })
+    .unwrap();
+}
+
+fn clean_tmp(out: &Path) {
+    for course in fs::read_dir(out).into_iter().flatten().flatten() {
+        for entry in fs::read_dir(course.path()).into_iter().flatten().flatten() {
+            if entry.file_name().to_string_lossy().starts_with("tmp_") {
+                fs::remove_dir_all(entry.path()).unwrap_or(());
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_chapter(
+    mut chapter: PathBuf,
+    name: &str,
+    out: &Path,
+    key: &str,
+    extras: Extras,
+    manifest: &Manifest,
+    progress: &Progress,
+    sx: &Sender<Job>,
+) -> Option<Outcome> {
+    let ring_str = format!("tmp_{name}.mp4");
+    let p = &ring_str[4..];
+    let tmp = &ring_str[..(ring_str.len() - 4)];
+
+    let stamp = Stamp::new(&chapter).unwrap();
+    let mut out = out.join(p);
+    let done = out.exists() && manifest.is_done(key, &stamp);
+    out.pop();
+    // the extras are still written next to videos already there
+    if done && !(extras.html || extras.subs || extras.timeline) {
+        fs::remove_dir_all(out.join(tmp)).unwrap_or(());
+        return Some(Outcome::Skipped);
+    }
+
+    chapter.push("0.json");
+    let mut data = match serde_json::from_reader(BufReader::new(File::open(&chapter).unwrap())) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{e}\n{:?}", chapter.as_os_str());
+            BufWriter::new(File::create(out.join(format!("broken_{name}"))).unwrap())
+                .write_all(e.to_string().as_bytes())
+                .unwrap_or(());
+            return Some(Outcome::Broken);
+        }
+    };
+    chapter.pop();
+    let log = prepare(&mut data)
+        .iter()
+        .map(|r| format!("{r}\n"))
+        .collect::<String>();
+    if !log.is_empty() {
+        eprint!("repaired {key}:\n{log}");
+        fs::write(out.join(format!("repaired_{name}")), log).unwrap_or(());
+    }
+    if extras.html {
+        write_player(&chapter, data.clone(), &out.join(format!("{name}.html"))).unwrap_or(());
+    }
+    if extras.subs {
+        write_subs(&data, &out.join(format!("{name}.vtt"))).unwrap_or(());
+    }
+    if extras.timeline {
+        let json = out.join(format!("{name}.timeline.json"));
+        write_timeline(data.clone(), Some(&json)).unwrap_or(());
+    }
+    if done {
+        fs::remove_dir_all(out.join(tmp)).unwrap_or(());
+        return Some(Outcome::Skipped);
+    }
+    manifest.invalidate(key);
+    let intrp = interpreter(data);
+
+    let tmp = out.join(tmp);
+    fs::create_dir_all(&tmp).unwrap();
+    let markers = Markers::new(intrp.data());
+    let (concat, t) = intrp.render(Counted(DirSink::new(&tmp), progress));
+    if cancelled() {
+        fs::remove_dir_all(&tmp).unwrap_or(());
+        return Some(Outcome::Cancelled);
+    }
+
+    out.push(p);
+    chapter.push("video.webm");
+    // same length ffmpeg_join cuts the video to
+    if let Some(end) = t.or_else(|| duration(&chapter)) {
+        fs::write(tmp.join(META), markers.ffmetadata(end)).unwrap();
+    }
+    let key = key.to_owned();
+    sx.send(Job { video: chapter, concat, out, tmp, t, key, stamp })
+        .unwrap();
+    None
+}
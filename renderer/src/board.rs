@@ -1,14 +1,21 @@
 #![allow(non_upper_case_globals)]
-use core::slice;
-use std::{collections::HashMap, f64::consts::PI, mem::take, path::Path};
+use std::{collections::HashMap, f64::consts::PI, path::Path};
 
-use image::{ImageBuffer, Rgba, RgbaImage};
+use image::{
+    imageops::{self, FilterType},
+    ImageBuffer, Rgba, RgbaImage,
+};
 use once_cell::sync::Lazy;
 use palette::{
     blend::{Blend, Compose, PreAlpha},
     LinSrgba,
 };
 
+use crate::{
+    instr::{Fen, Glyph},
+    rules::Status,
+};
+
 macro_rules! incl {
     ($(($t:tt $x:ident: $y:expr)),+) => {
         $(incl!(@dispatch $t $x $y);)+
@@ -22,7 +29,10 @@ macro_rules! incl {
     (@decl $x:ident $y:ident $z:expr, $b:expr) => {
         paste::paste!{
             pub static [<$x$y>]: Piece = Piece(
-                Lazy::new(|| image::open($z).unwrap().to_rgba8()), $b
+                Lazy::new(|| {
+                    let png = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $z));
+                    image::load_from_memory(png).unwrap().to_rgba8()
+                }), $b
             );
         }
     }
@@ -54,6 +64,7 @@ incl!(
 
 pieces!([White, Black] @ [Pawn, Knight, Bishop, Rook, Queen, King]);
 
+#[doc(hidden)]
 #[macro_export]
 macro_rules! comp {
     (eq, $a:expr, $b:expr) => {
@@ -97,72 +108,218 @@ macro_rules! br {
 #[derive(Debug)]
 pub struct Piece(Lazy<RgbaImage>, pub bool);
 
+#[derive(Clone)]
 pub struct Chessboard {
-    buf: Box<[Rgba<u8>]>,
     pub state: [[Option<&'static Piece>; 8]; 8],
     arrows: HashMap<[[u32; 2]; 2], Rgba<u8>>,
-    redraw_arrows: bool,
+    highlts: HashMap<[u32; 2], Rgba<u8>>,
+    circles: HashMap<[u32; 2], Rgba<u8>>,
+    fills: HashMap<[u32; 2], Rgba<u8>>,
+    glyphs: HashMap<[u32; 2], Glyph>,
+    // from and to squares of the move that led here, tinted under everything else and left alone
+    // by the markers
+    last_move: Option<[[u32; 2]; 2]>,
+    // the rest of the FEN, `true` being black to move
+    pub(crate) turn: bool,
+    pub(crate) castling: [bool; 4],
+    pub(crate) ep: Option<[u32; 2]>,
+    pub(crate) halfmove: u32,
+    pub(crate) fullmove: u32,
 }
 
+static Squares: Lazy<Box<[Rgba<u8>]>> = Lazy::new(|| {
+    let colors_buf = [
+        Rgba([0x7D, 0x3E, 0x2F, 0xFF]),
+        Rgba([0xA6, 0x80, 0x67, 0xFF]),
+        Rgba([0x7D, 0x3E, 0x2F, 0xFF]),
+    ];
+    (0..Chessboard::LEN as u32)
+        .map(|i| {
+            let pos =
+                usize::from((i / Chessboard::BO_SIZE / Chessboard::SQ_SIZE).is_multiple_of(2));
+            colors_buf[pos..=(pos + 1)]
+                [usize::from(!(i % Chessboard::BO_SIZE / Chessboard::SQ_SIZE).is_multiple_of(2))]
+        })
+        .collect()
+});
+
 impl Chessboard {
-    pub const ARROWS_LAYER: usize = 1;
     pub const BO_SIZE: u32 = 536;
-    pub const HIGHLT_LAYER: usize = 2;
-    pub const IMGS: [usize; 3] = {
-        const fn a(n: usize) -> usize { Chessboard::LEN * n }
-        [a(0), a(1), a(2)]
-    };
+    // king and rook squares for each of KQkq
+    pub(crate) const CASTLING: [[[u32; 2]; 2]; 4] =
+        [[[5, 1], [8, 1]], [[5, 1], [1, 1]], [[5, 8], [8, 8]], [[5, 8], [1, 8]]];
+    // CHECK* and STALEMATE* are the glow under the king and what's laid over the board, see
+    // `render_status`
+    pub const CHECK: Rgba<u8> = Rgba([0xFF, 0x20, 0x10, 0xE0]);
+    pub const CHECKMATE: Rgba<u8> = Rgba([0x40, 0x00, 0x00, 0x70]);
+    pub const LAST_MOVE: Rgba<u8> = Rgba([0xE8, 0xD2, 0x4A, 0x80]);
     pub const LEN: usize = Self::BO_SIZE.pow(2) as usize;
     pub const SQ_N_E: u32 = 8;
     pub const SQ_SIZE: u32 = 67;
+    pub const STALEMATE: Rgba<u8> = Rgba([0x20, 0x20, 0x28, 0x70]);
+    pub const STALEMATE_KING: Rgba<u8> = Rgba([0xB0, 0xB0, 0xC8, 0xE0]);
 
     pub fn new() -> Self {
-        let buf = vec![Rgba([0u8; 4]); Self::LEN * 3].into_boxed_slice();
-        let mut board = Self {
+        Self {
             state: [[None; 8]; 8],
             arrows: HashMap::with_capacity(6),
-            buf,
-            redraw_arrows: true,
-        };
-        board.iter_pixels_mut(0).for_each(|(i, p)| {
-            let colors_buf = [
-                Rgba([0x7D, 0x3E, 0x2F, 0xFF]),
-                Rgba([0xA6, 0x80, 0x67, 0xFF]),
-                Rgba([0x7D, 0x3E, 0x2F, 0xFF]),
-            ];
-            let pos = usize::from(((i / Self::BO_SIZE) / Self::SQ_SIZE) % 2 == 0);
-            *p = colors_buf[pos..=(pos + 1)]
-                [usize::from(((i % Self::BO_SIZE) / Self::SQ_SIZE) % 2 != 0)];
-        });
-        board
+            highlts: HashMap::with_capacity(6),
+            circles: HashMap::new(),
+            fills: HashMap::new(),
+            glyphs: HashMap::new(),
+            last_move: None,
+            turn: false,
+            castling: [true; 4],
+            ep: None,
+            halfmove: 0,
+            fullmove: 1,
+        }
+    }
+
+    pub fn arrow(&mut self, coord: [[u32; 2]; 2], color: impl Into<Rgba<u8>>) {
+        self.arrows.insert(coord, color.into());
+    }
+
+    pub fn unarrow(&mut self, coord: &[[u32; 2]; 2]) { self.arrows.remove(coord); }
+
+    pub fn clear_arrows(&mut self) { self.arrows.clear(); }
+
+    pub fn highlt(&mut self, coord: [u32; 2], color: impl Into<Rgba<u8>>) {
+        self.highlts.insert(coord, color.into());
     }
 
-    pub fn img(&self, n: usize) -> &[Rgba<u8>] {
-        unsafe { slice::from_raw_parts(self.buf.as_ptr().add(Self::IMGS[n]), Self::LEN) }
+    pub fn unhighlt(&mut self, coord: &[u32; 2]) { self.highlts.remove(coord); }
+
+    // circles and filled squares are highlights too as far as clearing them goes
+    pub fn clear_highlt(&mut self) {
+        self.highlts.clear();
+        self.circles.clear();
+        self.fills.clear();
     }
 
-    pub fn img_mut(&mut self, n: usize) -> &mut [Rgba<u8>] {
-        unsafe { slice::from_raw_parts_mut(self.buf.as_mut_ptr().add(Self::IMGS[n]), Self::LEN) }
+    pub fn circle(&mut self, coord: [u32; 2], color: impl Into<Rgba<u8>>) {
+        self.circles.insert(coord, color.into());
     }
 
-    pub fn arrow(&mut self, coord: [[u32; 2]; 2], color: impl Into<Rgba<u8>>) {
-        self.arrows.insert(coord, color.into());
-        self.redraw_arrows = true;
+    pub fn fill(&mut self, coord: [u32; 2], color: impl Into<Rgba<u8>>) {
+        self.fills.insert(coord, color.into());
     }
 
-    pub fn unarrow(&mut self, coord: &[[u32; 2]; 2]) {
-        self.arrows.remove(coord);
-        self.redraw_arrows = true;
+    pub fn glyph(&mut self, coord: [u32; 2], glyph: Glyph) { self.glyphs.insert(coord, glyph); }
+
+    pub fn clear_glyphs(&mut self) { self.glyphs.clear() }
+
+    // everything on the square short of arrows
+    pub fn unmark(&mut self, coord: &[u32; 2]) {
+        self.unhighlt(coord);
+        self.circles.remove(coord);
+        self.fills.remove(coord);
+        self.glyphs.remove(coord);
     }
 
-    pub fn clear_arrows(&mut self) {
-        self.arrows.clear();
-        self.redraw_arrows = true;
+    pub fn set_last_move(&mut self, mov: Option<[[u32; 2]; 2]>) { self.last_move = mov; }
+
+    pub fn last_move(&self) -> Option<[[u32; 2]; 2]> { self.last_move }
+
+    pub fn arrows(&self) -> impl Iterator<Item = ([[u32; 2]; 2], Rgba<u8>)> + '_ {
+        self.arrows.iter().map(|(&c, &color)| (c, color))
     }
 
-    pub fn highlt(&mut self, coord: [u32; 2], color: impl Into<Rgba<u8>>) {
-        let color = color.into();
-        let layer = self.img_mut(Self::HIGHLT_LAYER);
+    pub fn highlts(&self) -> impl Iterator<Item = ([u32; 2], Rgba<u8>)> + '_ {
+        self.highlts.iter().map(|(&c, &color)| (c, color))
+    }
+
+    pub fn circles(&self) -> impl Iterator<Item = ([u32; 2], Rgba<u8>)> + '_ {
+        self.circles.iter().map(|(&c, &color)| (c, color))
+    }
+
+    pub fn fills(&self) -> impl Iterator<Item = ([u32; 2], Rgba<u8>)> + '_ {
+        self.fills.iter().map(|(&c, &color)| (c, color))
+    }
+
+    pub fn glyphs(&self) -> impl Iterator<Item = ([u32; 2], Glyph)> + '_ {
+        self.glyphs.iter().map(|(&c, &glyph)| (c, glyph))
+    }
+
+    // piece placement field of a FEN
+    pub fn placement(&self) -> String {
+        let mut fen = String::with_capacity(64);
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.state[file][rank] {
+                    Some(p) => {
+                        if empty > 0 {
+                            fen.push(char::from(b'0' + empty));
+                            empty = 0;
+                        }
+                        fen.push(p.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push(char::from(b'0' + empty));
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        fen
+    }
+
+    // full FEN, castling rights are dropped when the king or rook isn't at home anymore
+    pub fn fen(&self) -> String {
+        let at = |[f, r]: [u32; 2], c| {
+            self.state[f as usize - 1][r as usize - 1].is_some_and(|p| p.to_char() == c)
+        };
+        let castling = Self::CASTLING
+            .iter()
+            .zip(self.castling)
+            .zip("KQkq".chars())
+            .filter(|&((&[king, rook], right), c)| {
+                let (k, r) = if c.is_ascii_uppercase() { ('K', 'R') } else { ('k', 'r') };
+                right && at(king, k) && at(rook, r)
+            })
+            .map(|(_, c)| c)
+            .collect::<String>();
+        format!(
+            "{} {} {} {} {} {}",
+            self.placement(),
+            if self.turn { 'b' } else { 'w' },
+            if castling.is_empty() { "-" } else { &castling },
+            self.ep
+                .map_or_else(|| "-".to_owned(), crate::timeline::square),
+            self.halfmove,
+            self.fullmove
+        )
+    }
+
+    pub fn turn(&self) -> bool { self.turn }
+
+    // places every piece and takes the side to move, castling rights, en passant and clocks
+    // from the FEN, missing fields fall back to the ones of the starting position
+    pub fn set_fen(&mut self, fen: &Fen) {
+        for (c, p) in fen.iter() {
+            self.draw_piece(c, p);
+        }
+        let mut fields = fen.as_str().split_whitespace().skip(1);
+        self.turn = fields.next() == Some("b");
+        self.castling = match fields.next() {
+            Some(c) => ['K', 'Q', 'k', 'q'].map(|x| c.contains(x)),
+            None => [true; 4],
+        };
+        self.ep = fields.next().and_then(|sq| {
+            let [f @ b'a'..=b'h', r @ b'1'..=b'8'] = sq.as_bytes() else {
+                return None;
+            };
+            Some([u32::from(f - b'a' + 1), u32::from(r - b'0')])
+        });
+        self.halfmove = fields.next().and_then(|x| x.parse().ok()).unwrap_or(0);
+        self.fullmove = fields.next().and_then(|x| x.parse().ok()).unwrap_or(1);
+    }
+
+    fn draw_highlt(layer: &mut [Rgba<u8>], coord: [u32; 2], color: Rgba<u8>) {
         let bo_size = Self::BO_SIZE as usize;
         let sq_size = Self::SQ_SIZE as usize;
         let start = (coord[0] as usize - 1) * sq_size + (8 - coord[1] as usize) * bo_size * sq_size;
@@ -177,58 +334,88 @@ impl Chessboard {
             .for_each(|x| layer[x] = color);
     }
 
-    pub fn unhighlt(&mut self, coord: &[u32; 2]) { self.highlt(*coord, Rgba([0; 4])) }
-
-    pub fn clear_highlt(&mut self) { self.img_mut(Self::HIGHLT_LAYER).fill(Rgba([0; 4])) }
+    fn fill_square(layer: &mut [Rgba<u8>], coord: [u32; 2], color: Rgba<u8>) {
+        let bo_size = Self::BO_SIZE as usize;
+        let sq_size = Self::SQ_SIZE as usize;
+        let start = (coord[0] as usize - 1) * sq_size + (8 - coord[1] as usize) * bo_size * sq_size;
+        for i in 0..sq_size {
+            for px in &mut layer[start + bo_size * i..start + bo_size * i + sq_size] {
+                *px = Self::blend(*px, color);
+            }
+        }
+    }
 
-    #[cfg(test)]
-    pub fn draw_initial(&mut self) {
-        for j in 1..=Self::SQ_N_E {
-            self.draw_piece([j, 2], Some(&WhitePawn));
+    // a ring just inside the square, as thick as a highlight's outline
+    fn draw_circle(layer: &mut [Rgba<u8>], [f, r]: [u32; 2], color: Rgba<u8>) {
+        let [c_x, c_y] = [
+            Self::SQ_SIZE * (f - 1) + Self::SQ_SIZE / 2,
+            Self::SQ_SIZE * (Self::SQ_N_E - r) + Self::SQ_SIZE / 2,
+        ];
+        let (radius, th) = (Self::SQ_SIZE as f32 / 2.0 - 5.0, 2.5);
+        for y in c_y - Self::SQ_SIZE / 2..=c_y + Self::SQ_SIZE / 2 {
+            for x in c_x - Self::SQ_SIZE / 2..=c_x + Self::SQ_SIZE / 2 {
+                let d = (x as f32 - c_x as f32).hypot(y as f32 - c_y as f32);
+                // antialiased on both edges
+                let cover = (th + 0.5 - (d - radius).abs()).clamp(0.0, 1.0);
+                if cover > 0.0 {
+                    let mut ring = color;
+                    ring[3] = (f32::from(color[3]) * cover) as u8;
+                    let px = &mut layer[(x + y * Self::BO_SIZE) as usize];
+                    *px = Self::blend(*px, ring);
+                }
+            }
         }
-        self.draw_piece([1, 1], Some(&WhiteRook));
-        self.draw_piece([Self::SQ_N_E, 1], Some(&WhiteRook));
-        self.draw_piece([2, 1], Some(&WhiteKnight));
-        self.draw_piece([Self::SQ_N_E - 1, 1], Some(&WhiteKnight));
-        self.draw_piece([3, 1], Some(&WhiteBishop));
-        self.draw_piece([Self::SQ_N_E - 2, 1], Some(&WhiteBishop));
-        self.draw_piece([4, 1], Some(&WhiteQueen));
-        self.draw_piece([5, 1], Some(&WhiteKing));
-
-        for j in 1..=Self::SQ_N_E {
-            self.draw_piece([j, Self::SQ_N_E - 1], Some(&BlackPawn));
+    }
+
+    // a disc in the top right corner of the square with the glyph in white, lichess' colors
+    fn draw_badge(layer: &mut [Rgba<u8>], [f, r]: [u32; 2], glyph: Glyph) {
+        const BANG: [&str; 7] = ["##", "##", "##", "##", "##", "..", "##"];
+        const QUESTION: [&str; 7] = [".###.", "##.##", "...##", "..##.", "..##.", ".....", "..##."];
+        let color = match glyph {
+            Glyph::Good => Rgba([0x22, 0xAC, 0x38, 0xFF]),
+            Glyph::Mistake => Rgba([0xE6, 0x9F, 0x00, 0xFF]),
+            Glyph::Brilliant => Rgba([0x16, 0x82, 0x26, 0xFF]),
+            Glyph::Blunder => Rgba([0xDF, 0x53, 0x53, 0xFF]),
+            Glyph::Interesting => Rgba([0xEA, 0x45, 0xD8, 0xFF]),
+            Glyph::Dubious => Rgba([0x56, 0xB4, 0xE9, 0xFF]),
+        };
+        let radius = 14.0f32;
+        let [c_x, c_y] = [Self::SQ_SIZE * f - 16, Self::SQ_SIZE * (Self::SQ_N_E - r) + 16];
+        for y in c_y - 16..=c_y + 16 {
+            for x in c_x - 16..=c_x + 16 {
+                let d = (x as f32 - c_x as f32).hypot(y as f32 - c_y as f32);
+                let cover = (radius + 0.5 - d).clamp(0.0, 1.0);
+                if cover > 0.0 {
+                    let mut disc = color;
+                    disc[3] = (255.0 * cover) as u8;
+                    layer[(x + y * Self::BO_SIZE) as usize] = disc;
+                }
+            }
         }
-        self.draw_piece([1, Self::SQ_N_E], Some(&BlackRook));
-        self.draw_piece([Self::SQ_N_E, Self::SQ_N_E], Some(&BlackRook));
-        self.draw_piece([2, Self::SQ_N_E], Some(&BlackKnight));
-        self.draw_piece([Self::SQ_N_E - 1, Self::SQ_N_E], Some(&BlackKnight));
-        self.draw_piece([3, Self::SQ_N_E], Some(&BlackBishop));
-        self.draw_piece([Self::SQ_N_E - 2, Self::SQ_N_E], Some(&BlackBishop));
-        self.draw_piece([4, Self::SQ_N_E], Some(&BlackQueen));
-        self.draw_piece([5, Self::SQ_N_E], Some(&BlackKing));
-    }
-
-    fn get_square(
-        &mut self,
-        [x, y]: [u32; 2],
-        z: usize,
-    ) -> impl Iterator<Item = (u32, &mut Rgba<u8>)> {
-        self.iter_pixels_mut(z).filter_map(move |(i, p)| {
-            if (i % Self::BO_SIZE) < Self::SQ_SIZE * x
-                && (i % Self::BO_SIZE) >= Self::SQ_SIZE * (x - 1)
-                && i / Self::BO_SIZE >= (Self::SQ_N_E - y) * Self::SQ_SIZE
-                && i / Self::BO_SIZE < (Self::SQ_N_E - (y - 1)) * Self::SQ_SIZE
-            {
-                Some((i, p))
-            } else {
-                None
+
+        // 2px cells, a blank column between characters
+        let chars = glyph
+            .as_str()
+            .chars()
+            .map(|c| if c == '!' { BANG } else { QUESTION })
+            .collect::<Vec<_>>();
+        let width = chars.iter().map(|c| c[0].len() as u32 + 1).sum::<u32>() - 1;
+        let mut x_0 = c_x - width;
+        for rows in chars {
+            for (i, row) in rows.iter().enumerate() {
+                for (j, _) in row.bytes().enumerate().filter(|(_, b)| *b == b'#') {
+                    let [x, y] = [x_0 + 2 * j as u32, c_y - 7 + 2 * i as u32];
+                    for [x, y] in [[x, y], [x + 1, y], [x, y + 1], [x + 1, y + 1]] {
+                        layer[(x + y * Self::BO_SIZE) as usize] = Rgba([0xFF; 4]);
+                    }
+                }
             }
-        })
+            x_0 += 2 * (rows[0].len() as u32 + 1);
+        }
     }
 
     pub fn draw_line(
-        &mut self,
-        img: usize,
+        layer: &mut [Rgba<u8>],
         color: impl Into<Rgba<u8>>,
         (th_0, th_1): (impl Iterator<Item = i32>, impl Iterator<Item = i32>),
         [[a_x, a_y], [b_x, b_y]]: [[i32; 2]; 2],
@@ -240,8 +427,7 @@ impl Chessboard {
                 Self::bresenham([[a_x + i_x, a_y + i_y], [b_x + i_x, b_y + i_y]])
             })
             .for_each(|[x, y]| {
-                self.img_mut(img)[(x.unsigned_abs() + y.unsigned_abs() * Self::BO_SIZE) as usize] =
-                    color;
+                layer[(x.unsigned_abs() + y.unsigned_abs() * Self::BO_SIZE) as usize] = color;
             });
     }
 
@@ -291,7 +477,8 @@ impl Chessboard {
         self.state[r as usize - 1][c as usize - 1] = piece;
     }
 
-    pub fn move_piece(&mut self, [[x_r, x_c], [y_r, y_c]]: [[u32; 2]; 2]) {
+    pub fn move_piece(&mut self, mov @ [[x_r, x_c], [y_r, y_c]]: [[u32; 2]; 2]) {
+        self.update_side(mov);
         let [x_r, x_c, y_r, y_c] = [x_r, x_c, y_r, y_c].map(|x| x as usize);
         if x_r == 5
             && (x_c == 1 || x_c == 8)
@@ -310,48 +497,49 @@ impl Chessboard {
         } else if self.state[y_r - 1][y_c - 1].is_none()
             && ((comp!(eq, self.state[x_r - 1][x_c - 1], &WhitePawn)
                 && comp!(eq, self.state[y_r - 1][x_c - 1], &BlackPawn))
-                || (comp!(eq, self.state[x_r - 1][x_c - 1], &WhitePawn)
-                    && comp!(eq, self.state[y_r - 1][x_c - 1], &BlackPawn)))
+                || (comp!(eq, self.state[x_r - 1][x_c - 1], &BlackPawn)
+                    && comp!(eq, self.state[y_r - 1][x_c - 1], &WhitePawn)))
         {
             self.state[y_r - 1][x_c - 1] = None;
         }
         self.state[y_r - 1][y_c - 1] = self.state[x_r - 1][x_c - 1].take();
     }
 
-    fn iter_pixels_mut(&mut self, i: usize) -> impl Iterator<Item = (u32, &mut Rgba<u8>)> {
-        self.img_mut(i)
-            .iter_mut()
-            .enumerate()
-            .map(|(i, p)| (i as u32, p))
-    }
-
-    fn prerender_arrows(&mut self) {
-        if !self.redraw_arrows {
+    // keeps the non-placement FEN fields up to date, must run before the move is applied
+    fn update_side(&mut self, mov @ [[f, r], [f2, r2]]: [[u32; 2]; 2]) {
+        let Some(piece) = self.state[f as usize - 1][r as usize - 1] else {
             return;
+        };
+        let pawn = piece.to_char().eq_ignore_ascii_case(&'p');
+        let capture = self.state[f2 as usize - 1][r2 as usize - 1].is_some();
+        self.turn = !piece.1;
+        if piece.1 {
+            self.fullmove += 1;
+        }
+        self.halfmove = if pawn || capture { 0 } else { self.halfmove + 1 };
+        self.ep = (pawn && r.abs_diff(r2) == 2).then_some([f, (r + r2) / 2]);
+        for (right, squares) in self.castling.iter_mut().zip(Self::CASTLING) {
+            if squares.iter().any(|sq| mov.contains(sq)) {
+                *right = false;
+            }
         }
-        self.img_mut(Self::ARROWS_LAYER).fill(Rgba([0; 4]));
-        let arrows = take(&mut self.arrows);
-        for (coord, color) in &arrows {
+    }
+
+    fn render_arrows(&self) -> Box<[Rgba<u8>]> {
+        let mut layer = vec![Rgba([0; 4]); Self::LEN].into_boxed_slice();
+        for (coord, color) in &self.arrows {
             let color = *color;
-            let mut p = |c| {
-                self.get_square(c, Chessboard::ARROWS_LAYER)
-                    .nth((Self::SQ_SIZE * (Self::SQ_SIZE - 1)) as usize)
-                    .unwrap()
-                    .0
-                    - Self::BO_SIZE * (Self::SQ_SIZE / 2) * 2
-            };
-            let separate_coord = |c: u32| {
-                let c_x = |n| (n % Self::BO_SIZE) as i32;
-                let c_y = |n| (n / Self::BO_SIZE) as i32;
-                [c_x(c), c_y(c)]
+            // top left corner of the square
+            let p = |[x, y]: [u32; 2]| {
+                [Self::SQ_SIZE * (x - 1), Self::SQ_SIZE * (Self::SQ_N_E - y)].map(|a| a as i32)
             };
             let c @ [[x1, y1], [x2, y2]] =
-                coord.map(|c| separate_coord(p(c)).map(|a| a + Self::SQ_SIZE as i32 / 2));
+                coord.map(|c| p(c).map(|a| a + Self::SQ_SIZE as i32 / 2));
             let th = -1..=1;
             if y1 == y2 {
-                self.draw_line(Self::ARROWS_LAYER, color, th!(y: -3..=3), c);
+                Self::draw_line(&mut layer, color, th!(y: -3..=3), c);
             } else {
-                self.draw_line(Self::ARROWS_LAYER, color, th!(x: -3..=3), c);
+                Self::draw_line(&mut layer, color, th!(x: -3..=3), c);
             }
             let a_offset = if x1 == x2 {
                 PI * f64::from(u8::from(y1 > y2))
@@ -403,16 +591,69 @@ impl Chessboard {
                     ]
                 })
                 .map(|c| {
-                    self.draw_line(Self::ARROWS_LAYER, color, th!(y: th.clone()), c);
+                    Self::draw_line(&mut layer, color, th!(y: th.clone()), c);
                     c[1]
                 });
-            self.draw_line(Self::ARROWS_LAYER, color, th!(y: th.clone()), s);
+            Self::draw_line(&mut layer, color, th!(y: th.clone()), s);
             for p in Self::bresenham(s) {
-                self.draw_line(Self::ARROWS_LAYER, color, th!(y: th.clone()), [c[1], p]);
+                Self::draw_line(&mut layer, color, th!(y: th.clone()), [c[1], p]);
+            }
+        }
+        layer
+    }
+
+    // what the side to move is in, nothing on boards without its king (lessons on bare pieces)
+    fn render_status(&self) -> Box<[Rgba<u8>]> {
+        let mut layer = vec![Rgba([0; 4]); Self::LEN].into_boxed_slice();
+        let Some(king) = self.king(self.turn) else {
+            return layer;
+        };
+        let (veil, glow) = match self.status() {
+            Status::Normal => return layer,
+            Status::Check => (None, Self::CHECK),
+            Status::Checkmate => (Some(Self::CHECKMATE), Self::CHECK),
+            Status::Stalemate => (Some(Self::STALEMATE), Self::STALEMATE_KING),
+        };
+        if let Some(veil) = veil {
+            layer.fill(veil);
+        }
+        Self::draw_glow(&mut layer, king, glow);
+        layer
+    }
+
+    // radial gradient out of the middle of the square, fading a bit past its edges
+    fn draw_glow(layer: &mut [Rgba<u8>], [f, r]: [u32; 2], color: Rgba<u8>) {
+        let radius = Self::SQ_SIZE as f32 * 0.6;
+        let [c_x, c_y] = [
+            Self::SQ_SIZE * (f - 1) + Self::SQ_SIZE / 2,
+            Self::SQ_SIZE * (Self::SQ_N_E - r) + Self::SQ_SIZE / 2,
+        ];
+        let span = |c: u32| c.saturating_sub(radius as u32)..(c + radius as u32).min(Self::BO_SIZE);
+        for y in span(c_y) {
+            for x in span(c_x) {
+                let d = (x as f32 - c_x as f32).hypot(y as f32 - c_y as f32) / radius;
+                if d >= 1.0 {
+                    continue;
+                }
+                let mut glow = color;
+                glow[3] = (f32::from(color[3]) * (1.0 - d * d)) as u8;
+                let px = &mut layer[(x + y * Self::BO_SIZE) as usize];
+                *px = Self::blend(*px, glow);
             }
         }
-        self.arrows = arrows;
-        self.redraw_arrows = false;
+    }
+
+    // `above` over `below`, straight alpha
+    fn blend(below: Rgba<u8>, above: Rgba<u8>) -> Rgba<u8> {
+        let [a_b, a_a] = [below[3], above[3]].map(|a| f32::from(a) / 255.0);
+        let a = a_a + a_b * (1.0 - a_a);
+        if a == 0.0 {
+            return Rgba([0; 4]);
+        }
+        let c = |i: usize| {
+            ((f32::from(above[i]) * a_a + f32::from(below[i]) * a_b * (1.0 - a_a)) / a) as u8
+        };
+        Rgba([c(0), c(1), c(2), (a * 255.0) as u8])
     }
 
     fn render_pieces(&self) -> impl Iterator<Item = &'static Rgba<u8>> + '_ {
@@ -440,31 +681,71 @@ impl Chessboard {
         StatePixelGetter(self, 0)
     }
 
-    pub fn render(&mut self) -> RgbaImage {
+    // two boards at half size next to each other, in a frame the size of one
+    pub fn render_pair(left: &Self, right: &Self) -> RgbaImage {
+        let half = Self::BO_SIZE / 2;
+        let mut frame = RgbaImage::new(Self::BO_SIZE, Self::BO_SIZE);
+        for (x, board) in [(0, left), (half, right)] {
+            let board = imageops::resize(&board.render(), half, half, FilterType::Triangle);
+            imageops::overlay(&mut frame, &board, x.into(), (half / 2).into());
+        }
+        frame
+    }
+
+    pub fn render(&self) -> RgbaImage {
         let mut render = ImageBuffer::new(Self::BO_SIZE, Self::BO_SIZE);
-        self.prerender_arrows();
+        let arrows = self.render_arrows();
+        let mut highlts = vec![Rgba([0; 4]); Self::LEN].into_boxed_slice();
+        for (&coord, &color) in &self.highlts {
+            Self::draw_highlt(&mut highlts, coord, color);
+        }
+        for (&coord, &color) in &self.circles {
+            Self::draw_circle(&mut highlts, coord, color);
+        }
+        // the last move and filled squares, over which the tint shows through
+        let mut fills = vec![Rgba([0; 4]); Self::LEN].into_boxed_slice();
+        for coord in self.last_move.into_iter().flatten() {
+            Self::fill_square(&mut fills, coord, Self::LAST_MOVE);
+        }
+        for (&coord, &color) in &self.fills {
+            Self::fill_square(
+                &mut fills,
+                coord,
+                Rgba([color[0], color[1], color[2], 0x80]),
+            );
+        }
+        let status = self.render_status();
+        let mut badges = vec![Rgba([0; 4]); Self::LEN].into_boxed_slice();
+        for (&coord, &glyph) in &self.glyphs {
+            Self::draw_badge(&mut badges, coord, glyph);
+        }
         render
             .pixels_mut()
-            .zip(self.img(0))
-            .zip(self.img(Self::HIGHLT_LAYER))
+            .zip(Squares.iter().zip(fills.iter()))
+            .zip(highlts.iter().zip(status.iter()))
             .zip(self.render_pieces())
-            .zip(self.img(Self::ARROWS_LAYER))
-            .for_each(|((((out, board), highlt), pieces), arrows)| {
-                // arrows[3] &= !highlt[3];
-                let [arrows, pieces, highlt, board] =
-                    [arrows, pieces, highlt, board].map(|Rgba(c)| {
-                        let [r, g, b, a] = c.map(|x| (x as f32) / 255.0);
-                        PreAlpha::from(LinSrgba::new(r, g, b, a))
-                    });
-                let (r, g, b) = pieces
-                    .over(highlt.over(board).overlay(arrows))
-                    .into_components();
-                *out = Rgba([r, g, b, 1.0].map(|x| (x * 255.0) as u8));
-            });
+            .zip(arrows.iter().zip(badges.iter()))
+            .for_each(
+                |((((out, (board, fills)), (highlt, status)), pieces), (arrows, badges))| {
+                    // arrows[3] &= !highlt[3];
+                    let [badges, arrows, pieces, status, highlt, fills, board] =
+                        [badges, arrows, pieces, status, highlt, fills, board].map(|Rgba(c)| {
+                            let [r, g, b, a] = c.map(|x| (x as f32) / 255.0);
+                            PreAlpha::from(LinSrgba::new(r, g, b, a))
+                        });
+                    let (r, g, b) = badges
+                        .over(
+                            pieces
+                                .over(status.over(highlt.over(fills.over(board))).overlay(arrows)),
+                        )
+                        .into_components();
+                    *out = Rgba([r, g, b, 1.0].map(|x| (x * 255.0) as u8));
+                },
+            );
         render
     }
 
-    pub fn save(&mut self, path: impl AsRef<Path>) {
+    pub fn save(&self, path: impl AsRef<Path>) {
         let img = self.render();
         img.save(path).unwrap();
     }
@@ -473,53 +754,3 @@ impl Chessboard {
 impl Default for Chessboard {
     fn default() -> Self { Self::new() }
 }
-
-impl Clone for Chessboard {
-    fn clone(&self) -> Self {
-        Self {
-            buf: self.buf.clone(),
-            state: self.state,
-            arrows: self.arrows.clone(),
-            redraw_arrows: self.redraw_arrows,
-        }
-    }
-
-    fn clone_from(&mut self, source: &Self) {
-        self.state = source.state;
-        unsafe {
-            std::ptr::copy_nonoverlapping(source.buf.as_ptr(), self.buf.as_mut_ptr(), Self::LEN * 3)
-        }
-        self.arrows.clone_from(&source.arrows);
-        self.redraw_arrows = source.redraw_arrows;
-    }
-}
-
-#[test]
-#[cfg(test)]
-pub fn test_board() {
-    let mut board = Chessboard::new();
-    board.draw_initial();
-    board.move_piece([[3, 2], [3, 3]]);
-    board.move_piece([[8, 1], [3, 3]]);
-    board.highlt([3, 3], Rgba([0xFF, 0x00, 0x00, 0x99]));
-    board.arrow([[8, 1], [3, 6]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[8, 2], [8, 1]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[2, 8], [3, 6]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [5, 3]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [1, 3]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [3, 5]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [3, 1]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [2, 4]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [4, 4]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [4, 2]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [2, 2]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [4, 5]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [2, 5]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [4, 1]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [2, 1]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [5, 4]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [1, 2]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [1, 4]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.arrow([[3, 3], [5, 2]], Rgba([0x00, 0xDD, 0x00, 0xFF]));
-    board.save("./a.png");
-}
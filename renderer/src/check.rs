@@ -0,0 +1,275 @@
+use std::{collections::HashSet, fmt};
+
+use crate::instr::{DataFile, Game, Instruction, InstructionData, MoveData};
+
+// what's wrong with a datafile that parsed, the interpreter gives up at (or quietly misdraws)
+// every one of these
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    // a marker for a game that wasn't shown yet, nothing to draw it on
+    NoBoard {
+        t: f64,
+        game_index: usize,
+    },
+    NoGame {
+        t: f64,
+        game_index: usize,
+    },
+    NoMove {
+        t: f64,
+        game_index: usize,
+        id: usize,
+    },
+    // `pm` pointing at a move that isn't there
+    Dangling {
+        game_index: usize,
+        id: usize,
+        pm: usize,
+    },
+    // moves leading back to themselves instead of to the start
+    Loop {
+        game_index: usize,
+        id: usize,
+    },
+    OutOfOrder {
+        t: f64,
+        prev: f64,
+    },
+    PastEnd {
+        t: f64,
+        end: f64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NoBoard { t, game_index } => {
+                write!(f, "{t}s: marker for game {game_index} before it's shown")
+            }
+            Self::NoGame { t, game_index } => write!(f, "{t}s: no game {game_index}"),
+            Self::NoMove { t, game_index, id } => {
+                write!(f, "{t}s: no move {id} in game {game_index}")
+            }
+            Self::Dangling { game_index, id, pm } => {
+                write!(f, "game {game_index}: move {id} follows missing move {pm}")
+            }
+            Self::Loop { game_index, id } => {
+                write!(
+                    f,
+                    "game {game_index}: move {id} never leads back to the start"
+                )
+            }
+            Self::OutOfOrder { t, prev } => write!(f, "{t}s: comes after {prev}s"),
+            Self::PastEnd { t, end } => write!(f, "{t}s: past the end of the video ({end}s)"),
+        }
+    }
+}
+
+impl Game {
+    // why `id` can't be traced back to the start, if it can't
+    pub fn unreachable(&self, game_index: usize, id: usize) -> Option<Problem> {
+        let mut line = vec![id];
+        loop {
+            let at = *line.last().unwrap();
+            let pm = self.moves.get(&at)?.prev_m;
+            // `pm` of the root is -1, or itself in some files
+            if pm == at || pm == usize::MAX {
+                return None;
+            } else if !self.moves.contains_key(&pm) {
+                return Some(Problem::Dangling { game_index, id: at, pm });
+            } else if let Some(i) = line.iter().position(|x| *x == pm) {
+                // named after its lowest move, the same whichever one it's reached from
+                let id = *line[i..].iter().min().unwrap();
+                return Some(Problem::Loop { game_index, id });
+            }
+            line.push(pm);
+        }
+    }
+}
+
+impl InstructionData {
+    pub fn game_index(&self) -> Option<usize> {
+        Some(match *self {
+            Self::HighlightSquare { game_index, .. }
+            | Self::DrawCircle { game_index, .. }
+            | Self::FillSquare { game_index, .. }
+            | Self::DrawGlyph { game_index, .. }
+            | Self::DrawArrow { game_index, .. }
+            | Self::Unarrow { game_index, .. }
+            | Self::UnarrowAll { game_index }
+            | Self::ClearAllHighlights { game_index }
+            | Self::GotoId { game_index, .. }
+            | Self::Unmark { game_index, .. }
+            | Self::UnmarkAll { game_index }
+            | Self::SelectGame { game_index, .. }
+            | Self::Move { game_index, .. } => game_index,
+            Self::Nop => return None,
+        })
+    }
+}
+
+// everything wrong with `data` for a video `end` seconds long (unchecked when `None`), in the
+// order the interpreter would run into it; moves are checked once each, cuepoints in order
+pub fn check(data: &DataFile, end: Option<f64>) -> Vec<Problem> {
+    let mut problems = vec![];
+    for (game_index, game) in data.games.iter().enumerate() {
+        let mut ids = game.moves.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        // every move past a broken one is broken the same way
+        for problem in ids
+            .into_iter()
+            .filter_map(|id| game.unreachable(game_index, id))
+        {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    let mut shown = HashSet::new();
+    let mut prev = None::<f64>;
+    for Instruction((t, instr)) in data.cuepoints.iter() {
+        let t = *t;
+        if let Some(prev) = prev.filter(|prev| t < *prev) {
+            problems.push(Problem::OutOfOrder { t, prev });
+        }
+        prev = Some(prev.map_or(t, |prev| prev.max(t)));
+        if let Some(end) = end.filter(|end| t >= *end) {
+            problems.push(Problem::PastEnd { t, end });
+        }
+        problems.extend(cuepoint(data, &mut shown, t, instr));
+    }
+    problems
+}
+
+// what the interpreter would trip over at this cuepoint, `shown` being the games that were by
+// then
+fn cuepoint(
+    data: &DataFile,
+    shown: &mut HashSet<usize>,
+    t: f64,
+    instr: &InstructionData,
+) -> Option<Problem> {
+    let game_index = instr.game_index()?;
+    let Some(game) = data.games.get(game_index) else {
+        return Some(Problem::NoGame { t, game_index });
+    };
+    let id = match *instr {
+        InstructionData::SelectGame { initial_move_id, .. } => initial_move_id,
+        InstructionData::GotoId { id, .. } => Some(id),
+        // moves bring their own position
+        InstructionData::Move { .. } => None,
+        _ if !shown.contains(&game_index) => return Some(Problem::NoBoard { t, game_index }),
+        _ => return None,
+    };
+    shown.insert(game_index);
+    id.filter(|id| !game.moves.contains_key(id))
+        .map(|id| Problem::NoMove { t, game_index, id })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fix {
+    // the cuepoint was put back in its place by time
+    Sorted,
+    // the cuepoint was dropped, the board stays as it was until the next one
+    Skipped,
+    // the move was hung from another one it can be played after
+    Reattached { pm: usize },
+    // the move was dropped, there's nowhere it can be played from
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+    pub problem: Problem,
+    pub fix: Fix,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.problem)?;
+        match self.fix {
+            Fix::Sorted => write!(f, "sorted"),
+            Fix::Skipped => write!(f, "skipped"),
+            Fix::Reattached { pm } if pm == usize::MAX => write!(f, "moved to the start"),
+            Fix::Reattached { pm } => write!(f, "moved after move {pm}"),
+            Fix::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+// fixes what `check` finds in place, short of the video's length: moves that can't be traced
+// back to the start are hung from the latest one they can be played after (or dropped), and
+// cuepoints are sorted, those pointing at missing games or moves skipped, so that a broken
+// segment doesn't take the rest of the video down with it
+pub fn repair(data: &mut DataFile) -> Vec<Repair> {
+    let mut repairs = vec![];
+    for (game_index, game) in data.games.iter_mut().enumerate() {
+        // each fix makes one more move reachable or drops it, so this ends
+        while let Some(problem) = {
+            let mut ids = game.moves.keys().copied().collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids.into_iter()
+                .find_map(|id| game.unreachable(game_index, id))
+        } {
+            let (Problem::Dangling { id, .. } | Problem::Loop { id, .. }) = problem else {
+                unreachable!()
+            };
+            let fix = match game.adopter(id) {
+                Some(pm) => {
+                    game.moves.get_mut(&id).unwrap().prev_m = pm;
+                    Fix::Reattached { pm }
+                }
+                None => {
+                    game.moves.remove(&id);
+                    Fix::Removed
+                }
+            };
+            repairs.push(Repair { problem, fix });
+        }
+    }
+
+    let mut cuepoints = std::mem::take(&mut data.cuepoints).into_vec();
+    let mut prev = None::<f64>;
+    for Instruction((t, _)) in &cuepoints {
+        if let Some(prev) = prev.filter(|prev| t < prev) {
+            repairs.push(Repair { problem: Problem::OutOfOrder { t: *t, prev }, fix: Fix::Sorted });
+        }
+        prev = Some(prev.map_or(*t, |prev| prev.max(*t)));
+    }
+    cuepoints.sort_by(|a, b| a.0 .0.total_cmp(&b.0 .0));
+    let mut shown = HashSet::new();
+    cuepoints.retain(|Instruction((t, instr))| {
+        let problem = cuepoint(data, &mut shown, *t, instr);
+        repairs.extend(problem.map(|problem| Repair { problem, fix: Fix::Skipped }));
+        problem.is_none()
+    });
+    data.cuepoints = cuepoints.into_boxed_slice();
+    repairs
+}
+
+impl Game {
+    // the latest move (by id, before `id` if there's any) that `id` can be played after, FENs
+    // go at the start
+    fn adopter(&self, id: usize) -> Option<usize> {
+        let MoveData::Coord(([from, to], _)) = self.moves.get(&id)?.data else {
+            return Some(usize::MAX);
+        };
+        let mut candidates = self
+            .moves
+            .keys()
+            .copied()
+            .filter(|c| *c != id && self.unreachable(usize::MAX, *c).is_none())
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|c| (*c < id, *c));
+        // the game's starting position last
+        candidates.insert(0, usize::MAX);
+        candidates.into_iter().rev().find(|&c| {
+            let board = self.position(c);
+            board.state[from[0] as usize - 1][from[1] as usize - 1]
+                .is_some_and(|p| p.1 == board.turn)
+                && board.legal_moves_from(from).contains(&to)
+        })
+    }
+}
@@ -6,21 +6,23 @@ use serde_json::{Map, Value};
 
 use crate::board::*;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct DataFile {
-    #[serde(skip)]
-    #[serde(rename(deserialize = "metadata"))]
-    _metadata: (),
+    #[serde(default, deserialize_with = "DataFile::de_metadata")]
+    pub metadata: Metadata,
     #[serde(deserialize_with = "DataFile::de_cuepoints")]
     pub cuepoints: Box<[Instruction]>,
-    #[serde(skip)]
-    #[serde(rename(deserialize = "exerciseGroup"))]
-    _exercise_groups: (),
     pub games: Box<[Game]>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Game {
+    pub name: Option<String>,
     pub init: Fen,
     pub moves: HashMap<usize, Move>,
 }
@@ -29,6 +31,7 @@ pub struct Game {
 pub struct Move {
     pub prev_m: usize,
     pub data: MoveData,
+    pub glyph: Option<Glyph>,
 }
 
 #[derive(Debug, Clone)]
@@ -37,7 +40,7 @@ pub enum MoveData {
     Coord(([[u32; 2]; 2], Option<usize>)),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Instruction(pub (f64, InstructionData));
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -49,6 +52,17 @@ pub enum Color {
     Red = 0xDB3328FF,
 }
 
+// move annotations, as PGN's first six NAGs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Good = 1,
+    Mistake,
+    Brilliant,
+    Blunder,
+    Interesting,
+    Dubious,
+}
+
 #[derive(Debug, Clone)]
 pub enum InstructionData {
     HighlightSquare {
@@ -56,6 +70,21 @@ pub enum InstructionData {
         coord: [u32; 2],
         game_index: usize,
     },
+    DrawCircle {
+        color: Color,
+        coord: [u32; 2],
+        game_index: usize,
+    },
+    FillSquare {
+        color: Color,
+        coord: [u32; 2],
+        game_index: usize,
+    },
+    DrawGlyph {
+        glyph: Glyph,
+        coord: [u32; 2],
+        game_index: usize,
+    },
     DrawArrow {
         color: Color,
         coord: [[u32; 2]; 2],
@@ -90,21 +119,57 @@ pub enum InstructionData {
         id: usize,
         mov: usize,
         fen: Fen,
+        glyph: Option<Glyph>,
         game_index: usize,
     },
     Nop,
 }
 
 impl Color {
-    fn from_str(str: &str) -> Self {
-        match str {
+    fn from_str(str: &str) -> Option<Self> {
+        Some(match str {
             "yellow" => Self::Yellow,
             "green" => Self::Green,
             "blue" => Self::Blue,
             "red" => Self::Red,
-            _ => panic!("{str}"),
+            _ => return None,
+        })
+    }
+}
+
+impl Glyph {
+    const ALL: [Self; 6] = [
+        Self::Good,
+        Self::Mistake,
+        Self::Brilliant,
+        Self::Blunder,
+        Self::Interesting,
+        Self::Dubious,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Good => "!",
+            Self::Mistake => "?",
+            Self::Brilliant => "!!",
+            Self::Blunder => "??",
+            Self::Interesting => "!?",
+            Self::Dubious => "?!",
         }
     }
+
+    // "?!", a NAG (3 or "$3"), or `None` for anything else
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let nag = match value {
+            Value::Number(n) => n.as_u64()?,
+            Value::String(s) => match s.strip_prefix('$') {
+                Some(n) => n.parse().ok()?,
+                None => return Self::ALL.into_iter().find(|g| g.as_str() == s),
+            },
+            _ => return None,
+        };
+        Self::ALL.into_iter().find(|g| *g as u64 == nag)
+    }
 }
 
 impl From<Color> for Rgba<u8> {
@@ -115,26 +180,50 @@ impl DataFile {
     fn de_cuepoints<'de, D: Deserializer<'de>>(d: D) -> Result<Box<[Instruction]>, D::Error> {
         Box::<[Instruction]>::deserialize(d)
     }
+
+    // only the bits we use, whatever else chess24 puts in there is ignored
+    fn de_metadata<'de, D: Deserializer<'de>>(d: D) -> Result<Metadata, D::Error> {
+        let metadata = Value::deserialize(d)?;
+        Ok(Metadata {
+            title: metadata
+                .get("title")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_owned),
+        })
+    }
 }
 
 macro_rules! get {
     ($d:ident, $n:expr, $t:ident) => {
         get!(@err $d.get($n).and_then(Value::$t), $n)
     };
-    ($d:ident, coords) => {
-        [get!($d, "x", as_u64) as u32, get!($d, "y", as_u64) as u32].map(|c| c + 1)
-    };
+    ($d:ident, coords) => {{
+        let [x, y] = [get!($d, "x", as_u64), get!($d, "y", as_u64)];
+        get!(@err (x < 8 && y < 8).then(|| [x as u32 + 1, y as u32 + 1]), "coords")
+    }};
     ($d:ident, line_as_can) => {
-        line_as_can2coord(get!($d, "lineAsCan", as_str)).0
+        get!(@err line_as_can2coord(get!($d, "lineAsCan", as_str)), "lineAsCan").0
     };
     ($d:ident, game_index) => {
         get!($d, "gameIndex", as_u64) as usize
     };
     ($d:ident, color) => {
-        Color::from_str(get!($d, "color", as_str))
+        get!(@err Color::from_str(get!($d, "color", as_str)), "color")
+    };
+    // optional, and left out when it isn't one we draw
+    ($d:ident, glyph) => {
+        ["glyph", "nag"]
+            .iter()
+            .find_map(|k| $d.get(*k))
+            .and_then(Glyph::from_value)
+    };
+    (@fen $f:expr) => {
+        Fen::parse($f).map_err(serde::de::Error::custom)?
     };
     ($d:ident, video_start_fen) => {
-        Fen::new(get!(@err $d.get("video_start_fen").and_then(Value::as_str), "video_start_fen").to_owned())
+        get!(@fen get!(@err $d.get("video_start_fen").and_then(Value::as_str), "video_start_fen"))
     };
     (@err $x:expr, $n: expr) =>{
     $x.ok_or_else(|| serde::de::Error::custom(format!("Error deserializing {}", $n)))?
@@ -147,6 +236,12 @@ impl<'de> Deserialize<'de> for Game {
     {
         let raw = Map::<String, Value>::deserialize(deserializer)?;
         Ok(Self {
+            name: ["name", "title"]
+                .iter()
+                .find_map(|k| raw.get(*k)?.as_str())
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_owned),
             init: get!(raw, video_start_fen),
             moves: {
                 let raw = get!(@err raw.get("moves"), "moves").as_array();
@@ -158,11 +253,14 @@ impl<'de> Deserialize<'de> for Game {
                             get!(x, "id", as_u64) as _,
                             Move {
                                 prev_m: get!(x, "pm", as_i64) as usize,
+                                glyph: get!(x, glyph),
                                 data: {
                                     if let Some(fen) = x.get("fen").and_then(Value::as_str) {
-                                        MoveData::Fen(Fen::new(fen.to_owned()))
+                                        MoveData::Fen(get!(@fen fen))
                                     } else {
-                                        MoveData::Coord(line_as_can2coord(get!(x, "m", as_str)))
+                                        MoveData::Coord(
+                                            get!(@err line_as_can2coord(get!(x, "m", as_str)), "m"),
+                                        )
                                     }
                                 },
                             },
@@ -208,6 +306,21 @@ impl InstructionData {
                     coord: get!(data, coords),
                     game_index: get!(data, game_index),
                 },
+                "drawCircle" => Self::DrawCircle {
+                    color: get!(data, color),
+                    coord: get!(data, coords),
+                    game_index: get!(data, game_index),
+                },
+                "fillSquare" => Self::FillSquare {
+                    color: get!(data, color),
+                    coord: get!(data, coords),
+                    game_index: get!(data, game_index),
+                },
+                "drawGlyph" => Self::DrawGlyph {
+                    glyph: get!(@err get!(data, glyph), "glyph"),
+                    coord: get!(data, coords),
+                    game_index: get!(data, game_index),
+                },
                 "drawArrow" => Self::DrawArrow {
                     color: get!(data, color),
                     coord: get!(data, line_as_can),
@@ -223,7 +336,8 @@ impl InstructionData {
                 "move" => Self::Move {
                     id: get!(data, "id", as_u64) as _,
                     mov: get!(data, "move", as_u64) as _,
-                    fen: Fen::new(get!(data, "fen", as_str).to_owned()),
+                    fen: get!(@fen get!(data, "fen", as_str)),
+                    glyph: get!(data, glyph),
                     game_index: get!(data, game_index),
                 },
                 "unarrow" => Self::Unarrow {
@@ -232,21 +346,28 @@ impl InstructionData {
                 },
                 "unarrowAll" => Self::UnarrowAll { game_index: get!(data, game_index) },
                 "triggerExerciseGroup" => Self::Nop, // uninmplemented
-                _ => unimplemented!("{:?}", raw.name),
+                name => {
+                    return Err(serde::de::Error::custom(format!(
+                        "Unknown cuepoint {name:?}"
+                    )))
+                }
             },
         ))
     }
 }
-fn line_as_can2coord(str: &str) -> ([[u32; 2]; 2], Option<usize>) {
-    let parse = |s: &str| {
-        let mut chars = s.chars();
-        let [l, n] = [chars.next(), chars.next()].map(Option::unwrap);
-        [l as u8 - b'a' + 1, n as u8 - b'0'].map(u32::from)
+// "e7e8q" into both squares and the piece promoted to, `None` if it isn't a move on the board
+pub fn line_as_can2coord(str: &str) -> Option<([[u32; 2]; 2], Option<usize>)> {
+    let square = |f: u8, r: u8| match [f, r] {
+        [b'a'..=b'h', b'1'..=b'8'] => Some([f - b'a' + 1, r - b'0'].map(u32::from)),
+        _ => None,
     };
-    (
-        [&str[0..2], &str[2..4]].map(parse),
-        str.chars().nth(4).and_then(Piece::uncolored),
-    )
+    let [f_0, r_0, f_1, r_1, rest @ ..] = str.as_bytes() else {
+        return None;
+    };
+    Some((
+        [square(*f_0, *r_0)?, square(*f_1, *r_1)?],
+        rest.first().and_then(|p| Piece::uncolored(char::from(*p))),
+    ))
 }
 
 #[derive(Debug, Clone)]
@@ -263,7 +384,40 @@ pub struct RefFen<'a> {
 }
 
 impl Fen {
-    fn new(fen: String) -> Self { Self(fen) }
+    pub fn new(fen: String) -> Self { Self(fen) }
+
+    // checks the piece placement and whose turn it is, the rest of the fields are optional and
+    // fall back to their defaults when unreadable
+    pub fn parse(fen: &str) -> Result<Self, String> {
+        let mut fields = fen.split_whitespace();
+        let ranks = fields
+            .next()
+            .unwrap_or_default()
+            .split('/')
+            .collect::<Vec<_>>();
+        if ranks.len() != 8 {
+            return Err(format!("FEN {fen:?}: {} ranks", ranks.len()));
+        }
+        for rank in ranks {
+            let mut files = 0;
+            for c in rank.chars().filter(|c| *c != '\\') {
+                files += match c {
+                    '1'..='8' => c as u32 - '0' as u32,
+                    _ if Piece::from_char(c).is_some() => 1,
+                    _ => return Err(format!("FEN {fen:?}: unexpected {c:?}")),
+                };
+            }
+            if files != 8 {
+                return Err(format!("FEN {fen:?}: {files} files in {rank:?}"));
+            }
+        }
+        match fields.next() {
+            None | Some("w" | "b") => Ok(Self(fen.to_owned())),
+            Some(turn) => Err(format!("FEN {fen:?}: unexpected turn {turn:?}")),
+        }
+    }
+
+    pub fn as_str(&self) -> &str { &self.0 }
 
     pub fn iter(&self) -> RefFen<'_> { RefFen { row: 8, column: 1, fen: &self.0, skip: 0, i: 0 } }
 }
@@ -271,45 +425,43 @@ impl Fen {
 impl<'a> Iterator for RefFen<'a> {
     type Item = ([u32; 2], Option<&'static Piece>);
 
+    // stops at whatever it can't make sense of, a `Fen` that passed `parse` goes through
+    // all 64 squares
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if self.skip > 0 {
+            if self.row == 0 {
+                break None;
+            } else if self.skip > 0 {
+                if self.column > 8 {
+                    self.row = 0;
+                    continue;
+                }
                 let ret = Some(([self.column, self.row], None));
                 self.skip -= 1;
                 self.column += 1;
                 break ret;
-            } else if self.row != 0 {
-                let c = self.fen.chars().nth(self.i).unwrap();
-                if c.is_ascii_digit() {
-                    self.skip = u32::from(c as u8 - b'0');
-                } else {
-                    let ret = Some((
-                        [self.column, self.row],
-                        Some(match Piece::from_char(c) {
-                            Some(p) => p,
-                            None => match c {
-                                '\\' => {
-                                    self.i += 1;
-                                    continue;
-                                }
-                                '/' | ' ' => {
-                                    self.column = 1;
-                                    self.row -= 1;
-                                    self.i += 1;
-                                    continue;
-                                }
-                                _ => unreachable!("{c}"),
-                            },
-                        }),
-                    ));
+            }
+            // the placement ends with the string too when there's nothing else
+            let c = self.fen.as_bytes().get(self.i).copied().unwrap_or(b' ');
+            self.i += 1;
+            match c {
+                b'1'..=b'8' => self.skip = u32::from(c - b'0'),
+                b'\\' => {}
+                b'/' | b' ' => {
+                    self.column = 1;
+                    self.row -= 1;
+                }
+                _ => {
+                    let Some(p) = Piece::from_char(char::from(c)).filter(|_| self.column <= 8)
+                    else {
+                        self.row = 0;
+                        break None;
+                    };
+                    let ret = Some(([self.column, self.row], Some(p)));
                     self.column += 1;
-                    self.i += 1;
                     break ret;
                 }
-            } else {
-                break None;
             }
-            self.i += 1;
         }
     }
 
@@ -336,4 +488,17 @@ impl Piece {
     pub fn from_uncolored(original: Option<&'static Self>, n: usize) -> Option<&'static Self> {
         original.map(|x| x.1 as u8 as usize).map(|c| Pieces[c][n])
     }
+
+    pub fn to_char(&'static self) -> char {
+        let n = Pieces[self.1 as usize]
+            .iter()
+            .position(|p| std::ptr::eq(*p, self))
+            .unwrap();
+        let c = b"PNBRQK"[n] as char;
+        if self.1 {
+            c.to_ascii_lowercase()
+        } else {
+            c
+        }
+    }
 }
@@ -1,213 +1,413 @@
 #![allow(unused_variables)]
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufWriter, Write},
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
 };
 
+use image::RgbaImage;
+
 use crate::{
     board::{Chessboard, Piece},
-    comp,
-    instr::{Color, DataFile, Game, Instruction, InstructionData, MoveData},
+    cancelled, comp,
+    instr::{DataFile, Fen, Game, Glyph, Instruction, InstructionData, MoveData},
     intrp::seal::TM,
-    str,
+    sink::{DirSink, FrameSink},
 };
 
+#[derive(Clone)]
+struct State {
+    timeline: TM,
+    // where each game shown so far was left, as an index into `timeline`
+    cursors: HashMap<usize, usize>,
+    // the game shown before the current one
+    other: Option<usize>,
+    pos: usize,
+}
+
 pub struct Interpreter {
     data: DataFile,
-    timeline: TM,
-    concat: String,
-    last_visited: HashMap<usize, usize>,
+    state: State,
+    checkpoints: Vec<State>,
+    end: Option<usize>,
+    two_boards: bool,
+}
+
+pub struct Snapshot {
+    pub board: Chessboard,
+    pub game_index: usize,
+    pub move_id: usize,
 }
 
 impl Interpreter {
-    pub fn new(data: DataFile) -> Self {
-        Self {
-            data,
-            timeline: TM::new(),
-            concat: String::with_capacity(16 * 1024),
-            last_visited: HashMap::new(),
+    const CHECKPOINT_EVERY: usize = 64;
+
+    pub fn new(mut data: DataFile) -> Self {
+        // seeking bisects the cuepoints by time, and frames last until the next one
+        data.cuepoints.sort_by(|a, b| a.0 .0.total_cmp(&b.0 .0));
+        let state = State { timeline: TM::new(), cursors: HashMap::new(), other: None, pos: 0 };
+        Self { data, checkpoints: vec![state.clone()], state, end: None, two_boards: false }
+    }
+
+    // once a second game is shown, frames have it at half size next to the current one (the
+    // lower game on the left), so lessons comparing two games show both
+    pub fn two_boards(self, two_boards: bool) -> Self { Self { two_boards, ..self } }
+
+    pub fn render_frames(self, out: impl AsRef<Path>) -> (PathBuf, Option<f64>) {
+        self.render(DirSink::new(out))
+    }
+
+    pub fn data(&self) -> &DataFile { &self.data }
+
+    pub fn render<S: FrameSink>(mut self, mut sink: S) -> (S::Output, Option<f64>) {
+        let two_boards = self.two_boards;
+        let t = self.run(|t, d_t, state| sink.push_frame(Self::frame(state, two_boards), t, d_t));
+        (sink.finish(), t)
+    }
+
+    // (start, duration, state) of every frame `render` would produce
+    pub fn snapshots(&mut self) -> (Vec<(f64, f64, Snapshot)>, Option<f64>) {
+        let mut snapshots = Vec::with_capacity(self.data.cuepoints.len());
+        let t = self.run(|t, d_t, state| {
+            snapshots.extend(Self::snapshot(&mut state.timeline).map(|s| (t, d_t, s)))
+        });
+        (snapshots, t)
+    }
+
+    // runs every cuepoint from the start, stops early with its time on corrupted ones, and on
+    // cancellation (whatever was rendered by then is thrown away)
+    fn run(&mut self, mut f: impl FnMut(f64, f64, &mut State)) -> Option<f64> {
+        self.rewind(0);
+        let cuepoints = &self.data.cuepoints;
+        for (i, Instruction((t, _))) in cuepoints.iter().enumerate() {
+            if cancelled() || !Self::step(&mut self.state, &self.data) {
+                return Some(*t);
+            }
+            let next_t = cuepoints
+                .get(i + 1)
+                .map_or(t + 1.0, |Instruction((t, _))| *t);
+            f(*t, next_t - t, &mut self.state);
+        }
+        None
+    }
+
+    fn frame(state: &mut State, two_boards: bool) -> RgbaImage {
+        let other = state
+            .other
+            .filter(|_| two_boards)
+            .and_then(|g| Some((g, state.cursors.get(&g)?)));
+        match (other, state.timeline.get_key().copied()) {
+            (Some((other, &i)), Some([_, game])) => {
+                let [board, other_board] =
+                    [state.timeline.index(), i].map(|i| state.timeline.get_at(i).clone());
+                if game < other {
+                    Chessboard::render_pair(&board, &other_board)
+                } else {
+                    Chessboard::render_pair(&other_board, &board)
+                }
+            }
+            // markers and the like before any game was shown
+            (_, None) => Chessboard::new().render(),
+            _ => state.timeline.get().render(),
         }
     }
 
-    pub fn render_frames(mut self, out: impl AsRef<Path>) -> (PathBuf, Option<f64>) {
-        let mut out = out.as_ref().to_owned().canonicalize().unwrap();
-        let mut iter = self
+    fn snapshot(timeline: &mut TM) -> Option<Snapshot> {
+        let &[move_id, game_index] = timeline.get_key()?;
+        Some(Snapshot { board: timeline.get().clone(), game_index, move_id })
+    }
+
+    // the frame `render` would show at `t`, same as `state_at`
+    pub fn frame_at(&mut self, t: f64) -> Option<RgbaImage> {
+        self.state_at(t)?;
+        Some(Self::frame(&mut self.state, self.two_boards))
+    }
+
+    // board as shown at `t`, `None` before the first cuepoint or past a corrupted one
+    pub fn state_at(&mut self, t: f64) -> Option<Snapshot> {
+        let target = self
             .data
             .cuepoints
-            .into_vec()
-            .into_iter()
-            .enumerate()
-            .peekable();
-        let t = 'render: loop {
-            if let Some((i, Instruction((t, instr)))) = iter.next() {
-                let (next_t, mut end) = match iter.peek() {
-                    Some((_, Instruction((t, _)))) => (*t, false),
-                    None => (t + 1.0, true),
+            .partition_point(|Instruction((c_t, _))| *c_t <= t);
+        if target == 0 || self.end.is_some_and(|end| target > end) {
+            return None;
+        }
+        if self.state.pos > target {
+            self.rewind(target);
+        }
+        while self.state.pos < target {
+            if self.state.pos.is_multiple_of(Self::CHECKPOINT_EVERY)
+                && self
+                    .checkpoints
+                    .last()
+                    .is_some_and(|c| c.pos < self.state.pos)
+            {
+                self.checkpoints.push(self.state.clone());
+            }
+            if !Self::step(&mut self.state, &self.data) {
+                self.end = Some(self.state.pos);
+                return None;
+            }
+        }
+        Self::snapshot(&mut self.state.timeline)
+    }
+
+    fn rewind(&mut self, pos: usize) {
+        let i = self.checkpoints.partition_point(|c| c.pos <= pos) - 1;
+        self.state.clone_from(&self.checkpoints[i]);
+    }
+
+    // applies the next cuepoint, `false` if the datafile turns out to be corrupted
+    fn step(state: &mut State, DataFile { cuepoints, games, .. }: &DataFile) -> bool {
+        let Instruction((_, instr)) = &cuepoints[state.pos];
+        let shown = state.timeline.get_key().map(|&[_, game]| game);
+        match instr.clone() {
+            // WONTFIX: highlights integrated in gotoid
+            InstructionData::GotoId { id, game_index } => {
+                let Some(game) = games.get(game_index) else {
+                    return false;
+                };
+                Self::goto_id([id, game_index], state, game);
+            }
+            InstructionData::Move { id, mov, fen, glyph, game_index } => {
+                let mut board = state
+                    .board(game_index)
+                    .map_or_else(Chessboard::new, |b| b.clone());
+                board.clear_markers();
+                board.play_fen(&fen);
+                board.annotate(glyph);
+                state.timeline.insert([id, game_index], board);
+            }
+            // markers go on their own game's board, left alone if it wasn't shown yet
+            InstructionData::DrawArrow { color, coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.arrow(coord, color)
+                }
+            }
+            InstructionData::HighlightSquare { color, coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.highlt(coord, color)
+                }
+            }
+            InstructionData::DrawCircle { color, coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.circle(coord, color)
+                }
+            }
+            InstructionData::FillSquare { color, coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.fill(coord, color)
+                }
+            }
+            InstructionData::DrawGlyph { glyph, coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.glyph(coord, glyph)
+                }
+            }
+            InstructionData::Unmark { coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.unmark(&coord)
+                }
+            }
+            InstructionData::Unarrow { coord, game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.unarrow(&coord)
+                }
+            }
+            InstructionData::UnmarkAll { game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.clear_markers()
+                }
+            }
+            InstructionData::ClearAllHighlights { game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.clear_highlt()
+                }
+            }
+            InstructionData::UnarrowAll { game_index } => {
+                if let Some(b) = state.board(game_index) {
+                    b.clear_arrows()
+                }
+            }
+            InstructionData::SelectGame { initial_move_id, game_index } => {
+                // handle corrupted datafiles :'(
+                let Some(game) = games.get(game_index) else {
+                    return false;
                 };
-                let d_t = next_t - t;
-                match instr {
-                    // WONTFIX: highlights integrated in gotoid
-                    InstructionData::GotoId { id, game_index } => {
-                        Self::goto_id([id, game_index], &mut self.timeline, &mut self.data.games);
-                    }
-                    InstructionData::Move { id, mov, fen, game_index } => {
-                        let mut board = self.timeline.get().clone();
-                        board.clear_markers();
-
-                        let mut df = Vec::with_capacity(2);
-                        for (pos @ [r, c], p) in fen.iter() {
-                            if comp!(neq, board.state[r as usize - 1][c as usize - 1], p) {
-                                df.push((pos, p))
-                            }
-                            board.draw_piece(pos, p)
-                        }
-                        if df.len() > 3 || df.len() < 2 {
-                        } else if df[0].1.is_none() && df[1].1.is_some() {
-                            board.arrow([df[0].0, df[1].0], Color::Blue);
-                        } else if df[1].1.is_none() && df[0].1.is_some() {
-                            board.arrow([df[1].0, df[0].0], Color::Blue);
-                        }
-                        self.timeline.insert([id, game_index], board);
-                    }
-                    InstructionData::DrawArrow { color, coord, game_index } => {
-                        self.timeline.get().arrow(coord, color)
-                    }
-                    InstructionData::HighlightSquare { color, coord, game_index } => {
-                        self.timeline.get().highlt(coord, color)
-                    }
-                    InstructionData::Unmark { coord, game_index } => {
-                        self.timeline.get().unhighlt(&coord)
-                    }
-                    InstructionData::Unarrow { coord, game_index } => {
-                        self.timeline.get().unarrow(&coord)
-                    }
-                    InstructionData::UnmarkAll { game_index } => {
-                        self.timeline.get().clear_markers()
-                    }
-                    InstructionData::ClearAllHighlights { game_index } => {
-                        self.timeline.get().clear_highlt()
-                    }
-                    InstructionData::UnarrowAll { game_index } => {
-                        self.timeline.get().clear_arrows()
-                    }
-                    InstructionData::SelectGame { initial_move_id, game_index } => {
-                        // handle corrupted datafiles :'(
-                        let Some(game) = self.data.games.get_mut(game_index) else {
-                            break 'render Some(t);
-                        };
-
-                        if let Some([last_m, last_g]) = self.timeline.get_key().copied() {
-                            self.last_visited.insert(last_g, last_m);
-                        }
 
-                        if let Some(id) = initial_move_id {
-                            let mut board = Chessboard::new();
-                            for (c, p) in &mut game.init.iter() {
-                                board.draw_piece(c, p);
-                            }
-                            if let Some(&mut MoveData::Coord((c, p))) =
-                                game.moves.get_mut(&id).as_mut().map(|x| &mut x.data)
+                // back where the game was left, unless told otherwise or it wasn't shown yet
+                match (initial_move_id, state.cursors.get(&game_index)) {
+                    (None, Some(&i)) => unsafe { state.timeline.set_cursor(i) },
+                    // its starting position being the one at the selected move
+                    (Some(id), _) => {
+                        let mut board = game.position_at_start();
+                        if let Some(&MoveData::Coord((c, p))) = game.moves.get(&id).map(|x| &x.data)
+                        {
+                            board.set_last_move(Some(c));
+                            // only when the promoted pawn is already there, the board is the
+                            // one before the move otherwise
+                            let [f, r] = c[1].map(|x| x as usize - 1);
+                            if let Some(p) =
+                                p.and_then(|p| Piece::from_uncolored(board.state[f][r], p))
                             {
-                                board.arrow(c, Color::Blue);
-                                if let Some(p) = p {
-                                    board.draw_piece(
-                                        c[1],
-                                        Some(
-                                            Piece::from_uncolored(
-                                                board.state[c[1][0] as usize - 1]
-                                                    [c[1][1] as usize - 1],
-                                                p,
-                                            )
-                                            .unwrap(),
-                                        ),
-                                    );
-                                }
+                                board.draw_piece(c[1], Some(p));
                             }
-                            self.timeline.insert([id, game_index], board);
-                        } else {
-                            self.timeline.index_of(&[
-                                *self.last_visited.get(&game_index).unwrap(),
-                                game_index,
-                            ]);
                         }
+                        state.timeline.insert([id, game_index], board);
                     }
-                    InstructionData::Nop => {}
-                };
-                out.push(&format!("{i}.png"));
-                self.timeline.get().save(&out);
-                // duplicate last, ffmpeg bug
-                'save: loop {
-                    self.concat
-                        .push_str(&format!("file '{}'\nduration {d_t}\n", str(&out)));
-                    end = !end;
-                    if end {
-                        break 'save;
+                    // the first time round at its first move, played the same as a gotoId
+                    (None, None) => {
+                        let id = game.main_line().first().copied().unwrap_or_default();
+                        Self::goto_id([id, game_index], state, game);
                     }
                 }
-                out.pop();
-            } else {
-                break 'render None;
             }
+            InstructionData::Nop => {}
         };
-        out.push("concat.txt");
-        BufWriter::new(File::create(&out).unwrap())
-            .write_all(self.concat.as_bytes())
-            .unwrap();
-        (out, t)
+        if let Some(&[_, game]) = state.timeline.get_key() {
+            state.cursors.insert(game, state.timeline.index());
+            if shown.is_some_and(|shown| shown != game) {
+                state.other = shown;
+            }
+        }
+        state.pos += 1;
+        true
     }
 
     #[inline]
-    fn goto_id(
-        mov @ [id, game_index]: [usize; 2],
-        timeline: &mut TM,
-        games: &mut Box<[Game]>,
-    ) -> usize {
-        if timeline.index_of(&mov).is_none() {
-            match games[game_index].moves.get(&id) {
-                Some(m) if id != m.prev_m => {
-                    let i = Self::goto_id([m.prev_m, game_index], timeline, games);
-                    unsafe { timeline.set_cursor(i) };
-                }
-                _ => {}
+    fn goto_id(mov @ [id, game_index]: [usize; 2], state: &mut State, game: &Game) {
+        if state.timeline.index_of(&mov).is_none() {
+            // played on from the latest move of its line already shown, otherwise from where the
+            // game was left (its starting position if it wasn't shown yet)
+            let line = game.line(id);
+            let known = line
+                .iter()
+                .rposition(|&m| state.timeline.index_of(&[m, game_index]).is_some());
+            let mut board = match known {
+                Some(_) => state.timeline.get().clone(),
+                None => state
+                    .board(game_index)
+                    .map_or_else(|| game.position_at_start(), |b| b.clone()),
             };
-            let mut board = timeline.get().clone();
-            Self::mov(&mut board, games, mov);
-            timeline.insert(mov, board);
+            for &m in &line[known.map_or(0, |i| i + 1)..] {
+                Self::mov(&mut board, game, m);
+                state.timeline.insert([m, game_index], board.clone());
+            }
+            // not a move of the game at all
+            if line.is_empty() {
+                Self::mov(&mut board, game, id);
+                state.timeline.insert(mov, board);
+            }
         }
-        timeline.index()
     }
 
-    fn mov(board: &mut Chessboard, games: &mut Box<[Game]>, [id, game_index]: [usize; 2]) {
+    pub(crate) fn mov(board: &mut Chessboard, game: &Game, id: usize) {
         board.clear_markers();
         let co = |board: &mut Chessboard, c| {
             board.move_piece(c);
-            board.arrow(c, Color::Blue);
+            board.set_last_move(Some(c));
         };
-        if let Some(mov) = games[game_index].moves.get_mut(&id) {
-            match &mut mov.data {
+        if let Some(mov) = game.moves.get(&id) {
+            match &mov.data {
                 MoveData::Coord((c, None)) => co(board, *c),
                 MoveData::Coord((c, Some(p))) => {
                     co(board, *c);
-                    board.draw_piece(
-                        c[1],
-                        Some(
-                            Piece::from_uncolored(
-                                board.state[c[1][0] as usize - 1][c[1][1] as usize - 1],
-                                *p,
-                            )
-                            .unwrap(),
-                        ),
-                    );
-                }
-                MoveData::Fen(fen) => {
-                    for (c, p) in fen.iter() {
-                        board.draw_piece(c, p);
+                    // nothing to promote when the move came from an empty square
+                    if let Some(p) = Piece::from_uncolored(
+                        board.state[c[1][0] as usize - 1][c[1][1] as usize - 1],
+                        *p,
+                    ) {
+                        board.draw_piece(c[1], Some(p));
                     }
                 }
+                MoveData::Fen(fen) => board.play_fen(fen),
+            }
+            board.annotate(mov.glyph);
+        }
+    }
+}
+
+impl Game {
+    // move ids from the root of the tree down to `id`
+    pub fn line(&self, id: usize) -> Vec<usize> {
+        let mut line = vec![id];
+        while let Some(m) = self.moves.get(line.last().unwrap()) {
+            // `pm` of the root is -1, or itself in some files, and broken ones may loop
+            if line.contains(&m.prev_m) {
+                break;
             }
+            line.push(m.prev_m);
         }
+        line.retain(|id| self.moves.contains_key(id));
+        line.reverse();
+        line
+    }
+
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        let line = self.line(id);
+        line.len().checked_sub(2).map(|i| line[i])
+    }
+
+    // sorted ids of the moves following each one, `None` holding the roots
+    pub fn children(&self) -> BTreeMap<Option<usize>, Vec<usize>> {
+        let mut children = BTreeMap::<_, Vec<_>>::new();
+        for &id in self.moves.keys() {
+            children.entry(self.parent(id)).or_default().push(id);
+        }
+        children.values_mut().for_each(|x| x.sort_unstable());
+        children
+    }
+
+    // first root down through every first child, the line chess24 shows as the game itself
+    pub fn main_line(&self) -> Vec<usize> {
+        let children = self.children();
+        let mut line = vec![];
+        let mut next = children.get(&None).and_then(|x| x.first());
+        while let Some(&id) = next {
+            line.push(id);
+            next = children.get(&Some(id)).and_then(|x| x.first());
+        }
+        line
+    }
+
+    // "12. Nf3" or "12... Nf6!", `None` for moves only given as a FEN
+    pub fn san(&self, id: usize) -> Option<String> {
+        let m = self.moves.get(&id)?;
+        let MoveData::Coord((mov, promotion)) = m.data else {
+            return None;
+        };
+        let board = match self.parent(id) {
+            Some(parent) => self.position(parent),
+            None => self.position_at_start(),
+        };
+        let dots = if board.turn { "..." } else { "." };
+        Some(format!(
+            "{}{dots} {}{}",
+            board.fullmove,
+            board.san(mov, promotion),
+            m.glyph.map_or("", Glyph::as_str)
+        ))
+    }
+
+    pub(crate) fn position_at_start(&self) -> Chessboard {
+        let mut board = Chessboard::new();
+        board.set_fen(&self.init);
+        board
+    }
+
+    pub fn position(&self, id: usize) -> Chessboard {
+        let mut board = self.position_at_start();
+        for id in self.line(id) {
+            Interpreter::mov(&mut board, self, id);
+        }
+        board
+    }
+}
+
+impl State {
+    // the board `game_index` was left at, `None` if it wasn't shown yet
+    fn board(&mut self, game_index: usize) -> Option<&mut Chessboard> {
+        let i = *self.cursors.get(&game_index)?;
+        Some(self.timeline.get_at(i))
     }
 }
 
@@ -216,6 +416,35 @@ impl Chessboard {
     fn clear_markers(&mut self) {
         self.clear_arrows();
         self.clear_highlt();
+        self.clear_glyphs();
+    }
+
+    // the move's glyph on the square it went to
+    fn annotate(&mut self, glyph: Option<Glyph>) {
+        if let (Some(glyph), Some([_, to])) = (glyph, self.last_move()) {
+            self.glyph(to, glyph);
+        }
+    }
+
+    // sets the position to `fen`, the last move being what changed on the board when it's a
+    // plain move (none for castling or a whole new position)
+    fn play_fen(&mut self, fen: &Fen) {
+        let mut df = Vec::with_capacity(2);
+        for (pos @ [r, c], p) in fen.iter() {
+            if comp!(neq, self.state[r as usize - 1][c as usize - 1], p) {
+                df.push((pos, p))
+            }
+        }
+        self.set_fen(fen);
+        self.set_last_move(if df.len() > 3 || df.len() < 2 {
+            None
+        } else if df[0].1.is_none() && df[1].1.is_some() {
+            Some([df[0].0, df[1].0])
+        } else if df[1].1.is_none() && df[0].1.is_some() {
+            Some([df[1].0, df[0].0])
+        } else {
+            None
+        });
     }
 }
 
@@ -226,6 +455,7 @@ mod seal {
 
     pub(super) type TM = CursoredAppendOnlyIM<[usize; 2], super::Chessboard>;
 
+    #[derive(Clone)]
     pub(super) struct CursoredAppendOnlyIM<K, V> {
         inner: IndexMap<K, V>,
         index: usize,
@@ -251,6 +481,9 @@ mod seal {
             unsafe { Some(self.inner.get_index_mut(self.index).unwrap_unchecked().0) }
         }
 
+        #[inline]
+        pub(super) fn get_at(&mut self, i: usize) -> &mut V { &mut self.inner[i] }
+
         #[inline]
         pub(super) fn insert(&mut self, k: K, v: V) { self.index = self.inner.insert_full(k, v).0; }
 
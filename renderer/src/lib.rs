@@ -0,0 +1,43 @@
+#![allow(
+    clippy::useless_transmute,
+    clippy::missing_transmute_annotations,
+    clippy::macro_metavars_in_unsafe
+)]
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub use board::Chessboard;
+pub use instr::{DataFile, Game};
+pub use intrp::{Interpreter, Snapshot};
+#[cfg(not(disable_ffmpeg))] pub use sink::FfmpegSink;
+pub use sink::{DirSink, Frame, FrameSink, GifSink, MemorySink, NullSink};
+
+pub mod anim;
+pub mod board;
+pub mod check;
+pub mod instr;
+pub mod intrp;
+pub mod markers;
+pub mod pgn;
+pub mod player;
+pub mod rules;
+pub mod sink;
+pub mod subs;
+pub mod thumb;
+pub mod timeline;
+pub mod video;
+
+// what the frames look like, bumped whenever rendering changes so that videos rendered before
+// are rendered again
+pub const RENDER_VERSION: u32 = 4;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+// long running work (ffmpeg mostly) bails out once this is set
+pub fn cancel() { CANCELLED.store(true, Ordering::Relaxed) }
+
+pub fn cancelled() -> bool { CANCELLED.load(Ordering::Relaxed) }
+
+pub(crate) fn str(x: &Path) -> &str { x.as_os_str().to_str().unwrap() }
@@ -1,32 +1,84 @@
-#![allow(clippy::useless_transmute)]
-#![cfg_attr(disable_ffmpeg, allow(unused_variables, unused_imports))]
+#![cfg_attr(disable_ffmpeg, allow(unused_variables, unused_imports, dead_code))]
 use std::{
     env,
     fs::{self, File},
-    io::{BufReader, BufWriter, Write},
-    panic::catch_unwind,
+    io::{self, BufReader, BufWriter},
     path::{Path, PathBuf},
-    thread::scope,
+    process,
 };
 
-use crossbeam::channel::{self, Sender};
-use intrp::Interpreter;
-use rayon::prelude::*;
-use video::duration;
-#[cfg(not(disable_ffmpeg))] use video::{ffmpeg_join, handle_ffmpeg};
+#[cfg(not(disable_ffmpeg))] use renderer::video::ffmpeg_join;
+use renderer::{
+    anim,
+    markers::Markers,
+    player, subs,
+    timeline::Timeline,
+    video::{duration, ffprobe, preflight},
+    DataFile, Interpreter,
+};
+
+mod batch;
+mod manifest;
+mod progress;
+mod report;
+mod site;
+mod validate;
 
-mod board;
-mod instr;
-mod intrp;
-mod video;
+const USAGE: &str = "\
+usage: renderer <input-dir> <output-dir> [--flags]
+       renderer html <chapter-dir> <out.html>
+       renderer timeline <chapter-dir> [<out.json>]
+       renderer anim <chapter-dir> <out.gif|out.apng|out.webp>
+       renderer subs <chapter-dir> <out.vtt|out.srt>
+       renderer chapters <chapter-dir> <video.mp4|video.mkv>
+       renderer validate <input-dir>
+       renderer site <courses-dir> <out-dir>";
 
 fn main() {
-    testing();
-    // prod();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    // the subcommand (or input dir) and the paths after it, `--flags` can go anywhere
+    let paths = args
+        .iter()
+        .filter(|a| !a.starts_with("--"))
+        .map(Path::new)
+        .collect::<Vec<_>>();
+    let path = |i: usize| paths.get(i).copied().unwrap_or_else(|| usage());
+    match paths.first().and_then(|x| x.to_str()) {
+        None if args.is_empty() => testing(),
+        Some("html") => or_exit(
+            data(path(1))
+                .and_then(|data| write_player(path(1), data, path(2)).map_err(|e| e.to_string())),
+        ),
+        Some("timeline") => or_exit(data(path(1)).and_then(|data| {
+            write_timeline(data, paths.get(2).copied()).map_err(|e| e.to_string())
+        })),
+        Some("anim") => or_exit(write_anim(path(1), path(2))),
+        Some("subs") => or_exit(
+            data(path(1)).and_then(|data| write_subs(&data, path(2)).map_err(|e| e.to_string())),
+        ),
+        Some("chapters") => or_exit(write_markers(path(1), path(2))),
+        Some("validate") => validate::run(path(1)),
+        Some("site") => or_exit(site::write(path(1), path(2), site::sheet_every())),
+        // anything else has to be an input dir, so that a typo doesn't start a render
+        _ if paths.len() == 2 && paths[0].is_dir() => batch::prod(),
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+fn or_exit(result: Result<(), impl std::fmt::Display>) {
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
+    }
 }
 
-#[allow(dead_code)]
 fn testing() {
+    check_tools(&[]);
     let (b, t) = Interpreter::new(
         serde_json::from_reader(BufReader::new(File::open("./0.json").unwrap())).unwrap(),
     )
@@ -40,88 +92,124 @@ fn testing() {
     );
 }
 
-#[allow(dead_code)]
-fn prod() {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(8)
-        .build_global()
-        .unwrap();
-    let out: PathBuf = env::args().nth(2).unwrap().into();
-    let (sx, ref rx) = channel::unbounded();
-    scope(move |s| {
-        #[cfg(not(disable_ffmpeg))]
-        (0..4).for_each(|_| drop(s.spawn(|| handle_ffmpeg(rx))));
-        fs::read_dir(env::args().nth(1).unwrap())
-            .unwrap()
-            .flat_map(|course| {
-                let course = course.unwrap();
-                let out2 = out.join(course.file_name());
-                fs::create_dir(&out2).unwrap_or(());
-                fs::read_dir(course.path())
-                    .unwrap()
-                    .map(move |x| (x.unwrap(), out2.clone()))
-            })
-            .par_bridge()
-            .for_each(|(chapter, out)| {
-                let file_name = chapter.file_name();
-                let name = file_name.to_string_lossy();
-                if let Err(e) = catch_unwind(|| handle_chapter(chapter.path(), &name, &out, &sx)) {
-                    BufWriter::new(File::create(out.join(format!("panic_{name}"))).unwrap())
-                        .write_all(e.downcast_ref::<&str>().unwrap_or(&"").as_bytes())
-                        .unwrap_or(());
-                }
-            });
-        drop(sx)
-    });
+// `encoders` are the ones the enabled outputs need on top of H.264
+fn check_tools(encoders: &[&str]) {
+    #[cfg(not(disable_ffmpeg))]
+    let preflight = || preflight().and_then(|t| t.require_encoders(encoders).map(|_| t));
+    match preflight() {
+        Ok(tools) => {
+            #[cfg(not(disable_ffmpeg))]
+            eprintln!(
+                "ffmpeg {} ({})",
+                tools.ffmpeg.version,
+                tools.ffmpeg.path.display()
+            );
+            eprintln!(
+                "ffprobe {} ({})",
+                tools.ffprobe.version,
+                tools.ffprobe.path.display()
+            );
+        }
+        Err(e) => {
+            eprintln!("preflight failed: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+fn data(chapter: &Path) -> Result<DataFile, String> {
+    let path = chapter.join("0.json");
+    File::open(&path)
+        .map_err(|e| format!("{}: {e}", path.display()))
+        .and_then(|f| serde_json::from_reader(BufReader::new(f)).map_err(|e| e.to_string()))
 }
 
-fn handle_chapter(
-    mut chapter: PathBuf,
-    name: &str,
-    out: &Path,
-    sx: &Sender<([PathBuf; 3], String, Option<f64>)>,
-) {
-    dbg!(&name);
-    let mut ring_str = format!("tmp_{name}.mp4");
-    let p = &ring_str[4..];
-    let tmp = &ring_str[..(ring_str.len() - 4)];
+// the page plays a link to (or copy of) the chapter's video sitting next to it
+fn write_player(chapter: &Path, data: DataFile, html: &Path) -> io::Result<()> {
+    let video = html.with_extension("webm");
+    if !video.exists() {
+        let src = chapter.join("video.webm");
+        fs::hard_link(&src, &video).or_else(|_| fs::copy(&src, &video).map(drop))?;
+    }
+    let url = player::href(&video.file_name().unwrap().to_string_lossy());
+    let title = html.file_stem().unwrap().to_string_lossy();
+    fs::write(html, player::export(&Timeline::new(data), &url, &title))
+}
 
-    let mut out = out.join(p);
-    chapter.push("video.webm");
-    if out.exists() && duration(&out).unwrap_or(0.0) >= duration(&chapter).unwrap() - 1.0 {
-        dbg!("PREV_DONE");
-        out.pop();
-        out.push(tmp);
-        fs::remove_dir_all(out).unwrap_or(());
-        return;
+// the resolved timeline as JSON (see TIMELINE.md), to stdout without `out`
+fn write_timeline(data: DataFile, out: Option<&Path>) -> serde_json::Result<()> {
+    let timeline = Timeline::new(data);
+    match out {
+        Some(out) => {
+            let file = File::create(out).map_err(serde_json::Error::io)?;
+            serde_json::to_writer_pretty(BufWriter::new(file), &timeline)
+        }
+        None => serde_json::to_writer_pretty(io::stdout().lock(), &timeline),
     }
-    out.pop();
-    chapter.pop();
+}
 
-    chapter.push("0.json");
-    let intrp = Interpreter::new(
-        match serde_json::from_reader(BufReader::new(File::open(&chapter).unwrap())) {
-            Ok(x) => x,
-            Err(e) => {
-                eprintln!("{e}\n{:?}", chapter.as_os_str());
-                BufWriter::new(File::create(out.join(format!("broken_{name}"))).unwrap())
-                    .write_all(e.to_string().as_bytes())
-                    .unwrap_or(());
-                return;
-            }
-        },
-    );
-    chapter.pop();
+// `--game=N` (0 by default), `--move=ID` (the main line otherwise), `--delay=SECONDS` per move,
+// `--no-arrows`
+fn write_anim(chapter: &Path, out: &Path) -> Result<(), String> {
+    let data = data(chapter)?;
+    let game_index = flag("game").map_or(Ok(0), |x| x.parse().map_err(|_| "bad --game"))?;
+    let id = flag("move")
+        .map(|x| x.parse().map_err(|_| "bad --move"))
+        .transpose()?;
+    let delay = flag("delay").map_or(Ok(1.0), |x| x.parse().map_err(|_| "bad --delay"))?;
+    let game = data
+        .games
+        .get(game_index)
+        .ok_or_else(|| format!("no game {game_index}"))?;
+    if id.is_some_and(|id| !game.moves.contains_key(&id)) {
+        return Err(format!("no move {} in game {game_index}", id.unwrap()));
+    }
+    let arrows = !env::args().any(|a| a == "--no-arrows");
+    anim::export(&anim::line(game, id, arrows), delay, out)
+}
 
-    out.push(tmp);
-    fs::create_dir_all(&out).unwrap();
-    let (v, t) = intrp.render_frames(&out);
-    out.pop();
+fn flag(name: &str) -> Option<String> {
+    env::args().find_map(|a| Some(a.strip_prefix(&format!("--{name}="))?.to_owned()))
+}
 
-    out.push(p);
-    chapter.push("video.webm");
-    ring_str.truncate(ring_str.len() - 4);
-    sx.send(([chapter, v, out], ring_str, t)).unwrap();
+// WebVTT, or SRT when `out` ends in .srt
+fn write_subs(data: &DataFile, out: &Path) -> io::Result<()> {
+    let cues = subs::cues(data);
+    let text = match out.extension().and_then(|x| x.to_str()) {
+        Some("srt") => subs::srt(&cues),
+        _ => subs::vtt(&cues),
+    };
+    fs::write(out, text)
 }
 
-fn str(x: &Path) -> &str { x.as_os_str().to_str().unwrap() }
+// into `out` in place when it's an MP4 or MKV, as an ffmetadata file otherwise
+fn write_markers(chapter: &Path, out: &Path) -> Result<(), String> {
+    let data = data(chapter)?;
+    let markers = Markers::new(&data);
+    let video = matches!(
+        out.extension().and_then(|x| x.to_str()),
+        Some("mp4" | "mkv")
+    );
+    let end = (ffprobe().is_ok())
+        .then(|| duration(if video { out.to_owned() } else { chapter.join("video.webm") }))
+        .flatten()
+        // the interpreter shows the last cuepoint for a second
+        .or_else(|| data.cuepoints.last().map(|x| x.0 .0 + 1.0))
+        .unwrap_or(0.0);
+    if !video {
+        return fs::write(out, markers.ffmetadata(end)).map_err(|e| e.to_string());
+    }
+    #[cfg(disable_ffmpeg)]
+    return Err("writing into videos needs ffmpeg".to_owned());
+    #[cfg(not(disable_ffmpeg))]
+    {
+        let meta = out.with_extension("ffmeta");
+        fs::write(&meta, markers.ffmetadata(end)).map_err(|e| e.to_string())?;
+        let code = renderer::video::ffmpeg_remux(out, None, Some(&meta));
+        fs::remove_file(&meta).unwrap_or(());
+        match code {
+            0 => Ok(()),
+            code => Err(format!("ffmpeg exited with {code}")),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use renderer::{video::SETTINGS, RENDER_VERSION};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    data: String,
+    video_size: u64,
+    video_mtime: u64,
+    version: String,
+    settings: String,
+}
+
+pub struct Manifest {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, Stamp>>,
+}
+
+impl Stamp {
+    pub fn new(chapter: &Path) -> io::Result<Self> {
+        let video = fs::metadata(chapter.join("video.webm"))?;
+        Ok(Self {
+            data: format!("{:016x}", fnv1a(&fs::read(chapter.join("0.json"))?)),
+            video_size: video.len(),
+            video_mtime: video
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+            version: RENDER_VERSION.to_string(),
+            settings: format!("{:016x}", fnv1a(settings().join(" ").as_bytes())),
+        })
+    }
+}
+
+// what the video is encoded with, and the flags changing what's rendered or muxed into it
+fn settings() -> Vec<&'static str> {
+    let flags = ["--repair", "--two-boards", "--chapters", "--mux-subs"]
+        .into_iter()
+        .filter(|f| env::args().any(|a| a == *f));
+    SETTINGS.into_iter().chain(flags).collect()
+}
+
+impl Manifest {
+    pub const FILE: &'static str = "manifest.json";
+
+    pub fn open(out: &Path) -> Self {
+        let path = out.join(Self::FILE);
+        let entries = File::open(&path)
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Self { path, entries: Mutex::new(entries) }
+    }
+
+    pub fn is_done(&self, key: &str, stamp: &Stamp) -> bool {
+        self.entries.lock().unwrap().get(key) == Some(stamp)
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(key).is_some() {
+            self.write(&entries);
+        }
+    }
+
+    pub fn complete(&self, key: String, stamp: Stamp) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, stamp);
+        self.write(&entries);
+    }
+
+    fn write(&self, entries: &BTreeMap<String, Stamp>) {
+        let tmp = self.path.with_extension("json.tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp).unwrap()), entries).unwrap();
+        fs::rename(tmp, &self.path).unwrap();
+    }
+}
+
+// stable across toolchains, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use crate::instr::{DataFile, Instruction, InstructionData};
+
+// one chapter per game the video switches to, titled after it, the video itself titled from the
+// datafile's metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Markers {
+    pub title: Option<String>,
+    pub chapters: Vec<(f64, String)>,
+}
+
+impl Markers {
+    pub fn new(data: &DataFile) -> Self {
+        let mut games = Vec::<(f64, usize)>::new();
+        for Instruction((t, instr)) in data.cuepoints.iter() {
+            if let InstructionData::SelectGame { game_index, .. } = *instr {
+                if game_index < data.games.len()
+                    && games.last().is_none_or(|&(_, last)| last != game_index)
+                {
+                    games.push((*t, game_index));
+                }
+            }
+        }
+        // whatever comes before the first game belongs to it
+        if let Some(first) = games.first_mut() {
+            first.0 = 0.0;
+        }
+        let chapters = games
+            .into_iter()
+            .map(|(t, i)| {
+                let name = data.games[i].name.clone();
+                (t, name.unwrap_or_else(|| format!("Game {}", i + 1)))
+            })
+            .collect();
+        Self { title: data.metadata.title.clone(), chapters }
+    }
+
+    // in ffmpeg's metadata format, for a video `end` seconds long
+    pub fn ffmetadata(&self, end: f64) -> String {
+        let mut meta = ";FFMETADATA1\n".to_owned();
+        if let Some(title) = &self.title {
+            writeln!(meta, "title={}", escape(title)).unwrap();
+        }
+        let ms = |t: f64| (t.max(0.0) * 1000.0).round() as u64;
+        for (i, (start, title)) in self.chapters.iter().enumerate() {
+            let stop = self.chapters.get(i + 1).map_or(end, |(t, _)| t.min(end));
+            if ms(stop) <= ms(*start) {
+                continue;
+            }
+            write!(
+                meta,
+                "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                ms(*start),
+                ms(stop),
+                escape(title)
+            )
+            .unwrap();
+        }
+        meta
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut out, c| {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                out.push('\\');
+            }
+            out.push(c);
+            out
+        })
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    instr::{DataFile, MoveData},
+    Chessboard, Game, Interpreter,
+};
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// every game of the chapter with all its variations, one after another
+pub fn export(data: &DataFile, title: &str) -> String {
+    data.games
+        .iter()
+        .enumerate()
+        .map(|(i, game)| self::game(game, title, i + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn game(game: &Game, title: &str, round: usize) -> String {
+    let children = game.children();
+
+    // chess24 roots the tree on a move holding the starting position more often than not
+    let mut board = Chessboard::new();
+    board.set_fen(&game.init);
+    let mut roots = children.get(&None).cloned().unwrap_or_default();
+    if let [root] = roots[..] {
+        if let MoveData::Fen(fen) = &game.moves[&root].data {
+            board.set_fen(fen);
+            roots = children.get(&Some(root)).cloned().unwrap_or_default();
+        }
+    }
+
+    let mut pgn = format!(
+        "[Event \"{}\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"{round}\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n",
+        title.replace('\\', "\\\\").replace('"', "\\\"")
+    );
+    let fen = board.fen();
+    if fen != START {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n"));
+    }
+    let mut tokens = vec![];
+    line(&mut tokens, board, game, &children, &roots);
+    tokens.push("*".to_owned());
+    pgn.push('\n');
+    pgn.push_str(&wrap(&tokens));
+    pgn.push('\n');
+    pgn
+}
+
+// main line first, every sibling of a move opens a variation right after it
+fn line<'a>(
+    tokens: &mut Vec<String>,
+    mut board: Chessboard,
+    game: &Game,
+    children: &'a BTreeMap<Option<usize>, Vec<usize>>,
+    mut siblings: &'a [usize],
+) {
+    let mut numbered = false;
+    while let Some((&main, variations)) = siblings.split_first() {
+        let before = board.clone();
+        numbered = token(tokens, &mut board, game, main, numbered);
+        for &v in variations {
+            tokens.push("(".to_owned());
+            line(tokens, before.clone(), game, children, &[v]);
+            tokens.push(")".to_owned());
+            numbered = false;
+        }
+        siblings = children.get(&Some(main)).map_or(&[], Vec::as_slice);
+    }
+}
+
+// `numbered` when black's move directly follows white's, which then needs no number
+fn token(
+    tokens: &mut Vec<String>,
+    board: &mut Chessboard,
+    game: &Game,
+    id: usize,
+    numbered: bool,
+) -> bool {
+    let numbered = match &game.moves[&id].data {
+        MoveData::Coord((mov, promotion)) => {
+            if !board.turn {
+                tokens.push(format!("{}.", board.fullmove));
+            } else if !numbered {
+                tokens.push(format!("{}...", board.fullmove));
+            }
+            tokens.push(board.san(*mov, *promotion));
+            if let Some(glyph) = game.moves[&id].glyph {
+                tokens.push(format!("${}", glyph as u8));
+            }
+            true
+        }
+        // positions that don't come from a move can only go in a comment
+        MoveData::Fen(fen) => {
+            tokens.push(format!("{{ {} }}", fen.as_str()));
+            false
+        }
+    };
+    Interpreter::mov(board, game, id);
+    numbered
+}
+
+fn wrap(tokens: &[String]) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        let glued = t == ")" || i > 0 && tokens[i - 1] == "(";
+        if width > 0 && width + 1 + t.len() > 79 {
+            out.push('\n');
+            width = 0;
+        } else if width > 0 && !glued {
+            out.push(' ');
+            width += 1;
+        }
+        out.push_str(t);
+        width += t.len();
+    }
+    out
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::timeline::Timeline;
+
+macro_rules! pieces {
+    ($($c:literal: $f:literal),+) => {
+        [$(($c, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $f)).as_slice())),+]
+    };
+}
+
+const TEMPLATE: &str = include_str!("./player.html");
+
+// self-contained page that plays `video` (as referenced from the page) next to an SVG board
+pub fn export(timeline: &Timeline, video: &str, title: &str) -> String {
+    let pieces = pieces!(
+        'K': "klt.png", 'Q': "qlt.png", 'R': "rlt.png", 'B': "blt.png", 'N': "nlt.png", 'P': "plt.png",
+        'k': "kdt.png", 'q': "qdt.png", 'r': "rdt.png", 'b': "bdt.png", 'n': "ndt.png", 'p': "pdt.png"
+    )
+    .map(|(c, png)| (c.to_string(), format!("data:image/png;base64,{}", STANDARD.encode(png))))
+    .into_iter()
+    .collect::<BTreeMap<_, _>>();
+    TEMPLATE
+        .replace("{{TITLE}}", &escape(title))
+        .replace("{{VIDEO}}", &escape(video))
+        .replace("{{PIECES}}", &script_json(&pieces))
+        .replace("{{TIMELINE}}", &script_json(timeline))
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// relative link to a file name, which here are chapter and course names full of spaces
+pub fn href(name: &str) -> String {
+    name.replace('%', "%25")
+        .replace('#', "%23")
+        .replace('?', "%3F")
+        .replace(' ', "%20")
+}
+
+// json can't close the <script> it's embedded in
+fn script_json(value: &impl serde::Serialize) -> String {
+    serde_json::to_string(value).unwrap().replace("</", "<\\/")
+}
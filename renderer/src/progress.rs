@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::report::Outcome;
+
+pub struct Progress {
+    start: Instant,
+    remaining: Mutex<HashMap<String, usize>>,
+    courses_total: usize,
+    courses_done: AtomicUsize,
+    chapters_total: usize,
+    chapters_done: AtomicUsize,
+    skipped: AtomicUsize,
+    frames: AtomicUsize,
+    // no more chapters coming, whether or not all of them were recorded
+    closed: AtomicBool,
+}
+
+impl Progress {
+    const LOG_EVERY: Duration = Duration::from_secs(30);
+    const TICK: Duration = Duration::from_millis(250);
+
+    pub fn new(courses: impl IntoIterator<Item = String>) -> Self {
+        let mut remaining = HashMap::<_, usize>::new();
+        let mut chapters_total = 0;
+        for c in courses {
+            *remaining.entry(c).or_default() += 1;
+            chapters_total += 1;
+        }
+        Self {
+            start: Instant::now(),
+            courses_total: remaining.len(),
+            remaining: Mutex::new(remaining),
+            courses_done: AtomicUsize::new(0),
+            chapters_total,
+            chapters_done: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+            frames: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn chapter_done(&self, key: &str, outcome: Outcome) {
+        let course = key.split('/').next().unwrap_or(key);
+        if let Some(n) = self.remaining.lock().unwrap().get_mut(course) {
+            *n -= 1;
+            if *n == 0 {
+                self.courses_done.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if let Outcome::Skipped = outcome {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.chapters_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frames(&self, n: usize) { self.frames.fetch_add(n, Ordering::Relaxed); }
+
+    pub fn finished(&self) -> bool {
+        self.chapters_done.load(Ordering::Relaxed) >= self.chapters_total
+            || self.closed.load(Ordering::Relaxed)
+    }
+
+    pub fn close(&self) { self.closed.store(true, Ordering::Relaxed) }
+
+    // redraws a status line on terminals, logs one every `LOG_EVERY` otherwise
+    pub fn display(&self, queue: impl Fn() -> usize, quiet: bool) {
+        let live = !quiet && io::stderr().is_terminal();
+        let mut last_log = Instant::now();
+        while !self.finished() {
+            thread::sleep(Self::TICK);
+            if live {
+                eprint!("\r\x1b[K{}", self.line(queue()));
+            } else if last_log.elapsed() >= Self::LOG_EVERY {
+                eprintln!("{}", self.line(queue()));
+                last_log = Instant::now();
+            }
+        }
+        if live {
+            eprint!("\r\x1b[K");
+        }
+        eprintln!("{}", self.line(queue()));
+        io::stderr().flush().unwrap_or(());
+    }
+
+    fn line(&self, queue: usize) -> String {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let done = self.chapters_done.load(Ordering::Relaxed);
+        let skipped = self.skipped.load(Ordering::Relaxed);
+        let frames = self.frames.load(Ordering::Relaxed);
+        // skipped chapters take no time, leave them out of the estimate (`skipped` is bumped
+        // first and read last, so it may be ahead of `done` for a moment)
+        let eta = match done.saturating_sub(skipped) {
+            0 => "?".to_owned(),
+            worked => {
+                let left =
+                    self.chapters_total.saturating_sub(done) as f64 * elapsed / worked as f64;
+                let left = left as u64;
+                format!("{}h{:02}m{:02}s", left / 3600, left / 60 % 60, left % 60)
+            }
+        };
+        format!(
+            "courses {}/{} | chapters {done}/{} ({skipped} skipped) | frames {frames} \
+             ({:.1}/s) | ffmpeg queue {queue} | ETA {eta}",
+            self.courses_done.load(Ordering::Relaxed),
+            self.courses_total,
+            self.chapters_total,
+            frames as f64 / elapsed.max(f64::EPSILON),
+        )
+    }
+}
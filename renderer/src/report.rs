@@ -0,0 +1,66 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::Serialize;
+
+use crate::progress::Progress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Rendered,
+    Skipped,
+    Broken,
+    Panicked,
+    Failed,
+    Cancelled,
+}
+
+pub struct Report {
+    path: PathBuf,
+    chapters: Mutex<BTreeMap<Outcome, Vec<String>>>,
+    progress: Progress,
+}
+
+impl Report {
+    pub const FILE: &'static str = "report.json";
+
+    pub fn new(out: &Path, progress: Progress) -> Self {
+        Self { path: out.join(Self::FILE), chapters: Mutex::default(), progress }
+    }
+
+    pub fn progress(&self) -> &Progress { &self.progress }
+
+    pub fn record(&self, outcome: Outcome, key: String) {
+        self.progress.chapter_done(&key, outcome);
+        self.chapters
+            .lock()
+            .unwrap()
+            .entry(outcome)
+            .or_default()
+            .push(key);
+    }
+
+    pub fn write(&self, interrupted: bool) {
+        #[derive(Serialize)]
+        struct Summary<'a> {
+            interrupted: bool,
+            chapters: &'a BTreeMap<Outcome, Vec<String>>,
+        }
+        let mut chapters = self.chapters.lock().unwrap();
+        chapters.values_mut().for_each(|v| v.sort_unstable());
+        for (outcome, keys) in chapters.iter() {
+            eprintln!("{outcome:?}: {}", keys.len());
+        }
+        serde_json::to_writer_pretty(
+            BufWriter::new(File::create(&self.path).unwrap()),
+            &Summary { interrupted, chapters: &chapters },
+        )
+        .unwrap();
+    }
+}
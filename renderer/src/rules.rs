@@ -0,0 +1,287 @@
+// move generation, just enough to name moves and to tell checks and mates apart
+use crate::{board::Piece, Chessboard};
+
+const KNIGHT: [[i32; 2]; 8] =
+    [[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2]];
+const KING: [[i32; 2]; 8] = [[1, 0], [1, 1], [0, 1], [-1, 1], [-1, 0], [-1, -1], [0, -1], [1, -1]];
+const ROOK: [[i32; 2]; 4] = [[1, 0], [0, 1], [-1, 0], [0, -1]];
+const BISHOP: [[i32; 2]; 4] = [[1, 1], [-1, 1], [-1, -1], [1, -1]];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Normal,
+    Check,
+    Checkmate,
+    Stalemate,
+}
+
+// index into PNBRQK
+pub fn kind(piece: &'static Piece) -> usize { Piece::uncolored(piece.to_char()).unwrap() }
+
+impl Chessboard {
+    fn get(&self, [f, r]: [u32; 2]) -> Option<&'static Piece> {
+        self.state[f as usize - 1][r as usize - 1]
+    }
+
+    fn offset([f, r]: [u32; 2], [df, dr]: [i32; 2]) -> Option<[u32; 2]> {
+        let [f, r] = [f as i32 + df, r as i32 + dr];
+        ((1..=8).contains(&f) && (1..=8).contains(&r)).then_some([f as u32, r as u32])
+    }
+
+    // squares the piece on `from` attacks, whether there's something on them or not
+    pub fn attacks(&self, from: [u32; 2]) -> Vec<[u32; 2]> {
+        let Some(piece) = self.get(from) else {
+            return vec![];
+        };
+        let jumps = |dirs: &[[i32; 2]]| -> Vec<_> {
+            dirs.iter().filter_map(|&d| Self::offset(from, d)).collect()
+        };
+        let slides = |dirs: &[[i32; 2]]| {
+            let mut squares = vec![];
+            for &d in dirs {
+                let mut sq = from;
+                while let Some(next) = Self::offset(sq, d) {
+                    squares.push(next);
+                    if self.get(next).is_some() {
+                        break;
+                    }
+                    sq = next;
+                }
+            }
+            squares
+        };
+        let forward = if piece.1 { -1 } else { 1 };
+        match kind(piece) {
+            0 => jumps(&[[1, forward], [-1, forward]]),
+            1 => jumps(&KNIGHT),
+            2 => slides(&BISHOP),
+            3 => slides(&ROOK),
+            4 => slides(&[ROOK, BISHOP].concat()),
+            _ => jumps(&KING),
+        }
+    }
+
+    pub fn attacked(&self, sq: [u32; 2], by_black: bool) -> bool {
+        (1..=8)
+            .flat_map(|f| (1..=8).map(move |r| [f, r]))
+            .filter(|&from| self.get(from).is_some_and(|p| p.1 == by_black))
+            .any(|from| self.attacks(from).contains(&sq))
+    }
+
+    pub fn king(&self, black: bool) -> Option<[u32; 2]> {
+        (1..=8)
+            .flat_map(|f| (1..=8).map(move |r| [f, r]))
+            .find(|&sq| self.get(sq).is_some_and(|p| p.1 == black && kind(p) == 5))
+    }
+
+    pub fn in_check(&self, black: bool) -> bool {
+        self.king(black).is_some_and(|k| self.attacked(k, !black))
+    }
+
+    // destinations of the piece on `from` ignoring checks, castling and en passant included
+    fn pseudo_moves(&self, from @ [f, r]: [u32; 2]) -> Vec<[u32; 2]> {
+        let Some(piece) = self.get(from) else {
+            return vec![];
+        };
+        let mut moves = self
+            .attacks(from)
+            .into_iter()
+            .filter(|&sq| self.get(sq).map_or(kind(piece) != 0, |p| p.1 != piece.1))
+            .collect::<Vec<_>>();
+        match kind(piece) {
+            0 => {
+                let forward = if piece.1 { -1 } else { 1 };
+                let start = if piece.1 { 7 } else { 2 };
+                if let Some(one) =
+                    Self::offset(from, [0, forward]).filter(|&x| self.get(x).is_none())
+                {
+                    moves.push(one);
+                    if r == start {
+                        moves.extend(
+                            Self::offset(one, [0, forward]).filter(|&x| self.get(x).is_none()),
+                        );
+                    }
+                }
+                if let Some(ep) = self
+                    .ep
+                    .filter(|&[ef, er]| ef.abs_diff(f) == 1 && er as i32 == r as i32 + forward)
+                {
+                    moves.push(ep);
+                }
+            }
+            5 if !self.in_check(piece.1) => {
+                let rights = if piece.1 { [2, 3] } else { [0, 1] };
+                for (i, [king, rook]) in rights.map(|i| (i, Chessboard::CASTLING[i])) {
+                    let (lo, hi) = (king[0].min(rook[0]), king[0].max(rook[0]));
+                    let step = if rook[0] > king[0] { 1 } else { -1 };
+                    let path = [0, 1].map(|n| [(king[0] as i32 + step * (n + 1)) as u32, r]);
+                    if from == king
+                        && self.castling[i]
+                        && self
+                            .get(rook)
+                            .is_some_and(|p| p.1 == piece.1 && kind(p) == 3)
+                        && (lo + 1..hi).all(|x| self.get([x, r]).is_none())
+                        && path.iter().all(|&sq| !self.attacked(sq, !piece.1))
+                    {
+                        moves.push(path[1]);
+                    }
+                }
+            }
+            _ => {}
+        }
+        moves
+    }
+
+    // destinations of the piece on `from` that don't leave its own king in check
+    pub fn legal_moves_from(&self, from: [u32; 2]) -> Vec<[u32; 2]> {
+        let Some(piece) = self.get(from) else {
+            return vec![];
+        };
+        self.pseudo_moves(from)
+            .into_iter()
+            .filter(|&to| {
+                let mut after = self.clone();
+                after.move_piece([from, to]);
+                !after.in_check(piece.1)
+            })
+            .collect()
+    }
+
+    pub fn legal_moves(&self, black: bool) -> Vec<[[u32; 2]; 2]> {
+        (1..=8)
+            .flat_map(|f| (1..=8).map(move |r| [f, r]))
+            .filter(|&from| self.get(from).is_some_and(|p| p.1 == black))
+            .flat_map(|from| {
+                self.legal_moves_from(from)
+                    .into_iter()
+                    .map(move |to| [from, to])
+            })
+            .collect()
+    }
+
+    // of the side to move
+    pub fn status(&self) -> Status {
+        match (
+            self.in_check(self.turn),
+            self.legal_moves(self.turn).is_empty(),
+        ) {
+            (true, true) => Status::Checkmate,
+            (true, false) => Status::Check,
+            (false, true) => Status::Stalemate,
+            (false, false) => Status::Normal,
+        }
+    }
+
+    // standard algebraic notation of a move played on this position, promotion being a PNBRQK index
+    pub fn san(
+        &self,
+        mov @ [from @ [f, r], to @ [f2, _]]: [[u32; 2]; 2],
+        promotion: Option<usize>,
+    ) -> String {
+        let Some(piece) = self.get(from) else {
+            return format!(
+                "{}{}",
+                crate::timeline::square(from),
+                crate::timeline::square(to)
+            );
+        };
+        let mut san = String::with_capacity(8);
+        let capture = self.get(to).is_some() || (kind(piece) == 0 && f != f2);
+        if kind(piece) == 5 && f.abs_diff(f2) == 2 {
+            san.push_str(if f2 > f { "O-O" } else { "O-O-O" });
+        } else if kind(piece) == 0 {
+            if capture {
+                san.push(char::from(b'a' + f as u8 - 1));
+            }
+        } else {
+            san.push(piece.to_char().to_ascii_uppercase());
+            let rivals = (1..=8)
+                .flat_map(|f| (1..=8).map(move |r| [f, r]))
+                .filter(|&sq| sq != from && self.get(sq).is_some_and(|p| std::ptr::eq(p, piece)))
+                .filter(|&sq| self.legal_moves_from(sq).contains(&to))
+                .collect::<Vec<_>>();
+            if !rivals.is_empty() {
+                let file = rivals.iter().all(|x| x[0] != f);
+                let rank = rivals.iter().all(|x| x[1] != r);
+                if file || !rank {
+                    san.push(char::from(b'a' + f as u8 - 1));
+                }
+                if !file {
+                    san.push(char::from(b'0' + r as u8));
+                }
+            }
+        }
+        if !san.starts_with('O') {
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&crate::timeline::square(to));
+        }
+        if let Some(p) = promotion {
+            san.push('=');
+            san.push(char::from(b"PNBRQK"[p]));
+        }
+        let mut after = self.clone();
+        after.move_piece(mov);
+        if let Some(p) = promotion {
+            after.draw_piece(to, Piece::from_uncolored(Some(piece), p));
+        }
+        if after.in_check(!piece.1) {
+            san.push(if after.legal_moves(!piece.1).is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{instr::Fen, Chessboard};
+
+    fn board(fen: &str) -> Chessboard {
+        let mut board = Chessboard::new();
+        board.set_fen(&Fen::new(fen.to_owned()));
+        board
+    }
+
+    #[test]
+    fn san() {
+        let start = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(start.san([[5, 2], [5, 4]], None), "e4");
+        assert_eq!(start.san([[7, 1], [6, 3]], None), "Nf3");
+        // two knights on the same rank, two rooks on the same file
+        let b = board("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1");
+        assert_eq!(b.san([[2, 1], [4, 2]], None), "Nbd2");
+        assert_eq!(b.san([[1, 5], [1, 3]], None), "R5a3");
+        // castling, en passant, promotion with check
+        let b = board("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        assert_eq!(b.san([[5, 1], [7, 1]], None), "O-O");
+        assert_eq!(b.san([[5, 1], [3, 1]], None), "O-O-O");
+        assert_eq!(b.san([[5, 5], [4, 6]], None), "exd6");
+        assert_eq!(b.san([[2, 7], [1, 8]], Some(4)), "bxa8=Q+");
+        // fool's mate
+        let b = board("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2");
+        assert_eq!(b.san([[4, 8], [8, 4]], None), "Qh4#");
+    }
+
+    #[test]
+    fn status() {
+        use super::Status::*;
+        assert_eq!(board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").status(), Stalemate);
+        assert_eq!(board("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").status(), Checkmate);
+        assert_eq!(board("7k/8/6K1/8/8/8/8/7Q b - - 0 1").status(), Check);
+        assert_eq!(
+            board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").status(),
+            Normal
+        );
+        // taking the checking pawn en passant is black's only way out
+        assert_eq!(
+            board("8/8/pp6/kp6/1Pp5/2N5/2N5/7K b - b3 0 1").status(),
+            Check
+        );
+        // pinned knight can't move, castling through an attacked square isn't allowed
+        let b = board("4r1k1/8/8/8/8/8/4N3/4K2R w K - 0 1");
+        assert!(b.legal_moves_from([5, 2]).is_empty());
+        let b = board("5rk1/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(!b.legal_moves_from([5, 1]).contains(&[7, 1]));
+    }
+}
@@ -0,0 +1,191 @@
+#[cfg(not(disable_ffmpeg))] use std::process::{Child, ChildStdin, Command, Stdio};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, RgbaImage,
+};
+
+use crate::str;
+#[cfg(not(disable_ffmpeg))] use crate::video::tools;
+
+pub trait FrameSink {
+    type Output;
+
+    fn push_frame(&mut self, frame: RgbaImage, start: f64, duration: f64);
+
+    fn finish(self) -> Self::Output;
+}
+
+// numbered PNGs plus a `concat.txt` for ffmpeg's concat demuxer
+pub struct DirSink {
+    out: PathBuf,
+    concat: String,
+    last: usize,
+    i: usize,
+}
+
+impl DirSink {
+    pub fn new(out: impl AsRef<Path>) -> Self {
+        Self {
+            out: out.as_ref().canonicalize().unwrap(),
+            concat: String::with_capacity(16 * 1024),
+            last: 0,
+            i: 0,
+        }
+    }
+}
+
+impl FrameSink for DirSink {
+    type Output = PathBuf;
+
+    fn push_frame(&mut self, frame: RgbaImage, _: f64, duration: f64) {
+        self.out.push(format!("{}.png", self.i));
+        frame.save(&self.out).unwrap();
+        self.last = self.concat.len();
+        self.concat
+            .push_str(&format!("file '{}'\nduration {duration}\n", str(&self.out)));
+        self.out.pop();
+        self.i += 1;
+    }
+
+    fn finish(mut self) -> PathBuf {
+        // duplicate last, ffmpeg bug
+        self.concat.extend_from_within(self.last..);
+        self.out.push("concat.txt");
+        BufWriter::new(File::create(&self.out).unwrap())
+            .write_all(self.concat.as_bytes())
+            .unwrap();
+        self.out
+    }
+}
+
+pub struct Frame {
+    pub image: RgbaImage,
+    pub start: f64,
+    pub duration: f64,
+}
+
+#[derive(Default)]
+pub struct MemorySink(Vec<Frame>);
+
+impl FrameSink for MemorySink {
+    type Output = Vec<Frame>;
+
+    fn push_frame(&mut self, image: RgbaImage, start: f64, duration: f64) {
+        self.0.push(Frame { image, start, duration });
+    }
+
+    fn finish(self) -> Vec<Frame> { self.0 }
+}
+
+// animated GIF looping forever, each frame shown for as long as it lasts
+pub struct GifSink(GifEncoder<BufWriter<File>>);
+
+impl GifSink {
+    // quantizing big boards at the best quality takes ages for no visible gain
+    const SPEED: i32 = 10;
+
+    pub fn new(out: impl AsRef<Path>) -> Self {
+        let mut encoder =
+            GifEncoder::new_with_speed(BufWriter::new(File::create(out).unwrap()), Self::SPEED);
+        encoder.set_repeat(Repeat::Infinite).unwrap();
+        Self(encoder)
+    }
+}
+
+impl FrameSink for GifSink {
+    type Output = ();
+
+    fn push_frame(&mut self, frame: RgbaImage, _: f64, duration: f64) {
+        let delay = Delay::from_numer_denom_ms((duration * 1000.0).round() as u32, 1);
+        self.0
+            .encode_frame(image::Frame::from_parts(frame, 0, 0, delay))
+            .unwrap();
+    }
+
+    fn finish(self) {}
+}
+
+pub struct NullSink;
+
+impl FrameSink for NullSink {
+    type Output = ();
+
+    fn push_frame(&mut self, _: RgbaImage, _: f64, _: f64) {}
+
+    fn finish(self) {}
+}
+
+// raw RGBA frames piped into ffmpeg at a constant rate, each one repeated for as long as it lasts
+#[cfg(not(disable_ffmpeg))]
+pub struct FfmpegSink {
+    out: PathBuf,
+    fps: u32,
+    args: Vec<String>,
+    child: Option<(Child, BufWriter<ChildStdin>)>,
+    written: u64,
+}
+
+#[cfg(not(disable_ffmpeg))]
+impl FfmpegSink {
+    pub fn new(out: impl AsRef<Path>, fps: u32, args: &[&str]) -> Self {
+        Self {
+            out: out.as_ref().to_owned(),
+            fps,
+            args: args.iter().map(|&a| a.to_owned()).collect(),
+            child: None,
+            written: 0,
+        }
+    }
+
+    fn spawn(&self, (w, h): (u32, u32)) -> (Child, BufWriter<ChildStdin>) {
+        #[rustfmt::skip]
+        let mut child = Command::new(&tools().ffmpeg.path)
+            .args([
+                "-nostdin",
+                "-loglevel", "error",
+                "-f", "rawvideo",
+                "-pix_fmt", "rgba",
+                "-s", &format!("{w}x{h}"),
+                "-r", &self.fps.to_string(),
+                "-i", "pipe:0",
+            ])
+            .args(&self.args)
+            .args(["-y", str(&self.out)])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = BufWriter::new(child.stdin.take().unwrap());
+        (child, stdin)
+    }
+}
+
+#[cfg(not(disable_ffmpeg))]
+impl FrameSink for FfmpegSink {
+    type Output = i32;
+
+    fn push_frame(&mut self, frame: RgbaImage, start: f64, duration: f64) {
+        if self.child.is_none() {
+            self.child = Some(self.spawn(frame.dimensions()));
+        }
+        let (_, stdin) = self.child.as_mut().unwrap();
+        // round against the absolute end so errors don't pile up over long videos, but never
+        // drop a frame entirely
+        let until = ((start + duration) * f64::from(self.fps)).round().max(0.0) as u64;
+        for _ in self.written..until.max(self.written + 1) {
+            stdin.write_all(frame.as_raw()).unwrap();
+        }
+        self.written = until.max(self.written + 1);
+    }
+
+    fn finish(self) -> i32 {
+        let Some((mut child, stdin)) = self.child else { return -1 };
+        drop(stdin.into_inner().ok());
+        child.wait().ok().and_then(|s| s.code()).unwrap_or(-1)
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+use rayon::prelude::*;
+use renderer::{
+    pgn,
+    player::{escape, href},
+    thumb,
+    video::{duration, ffprobe, preflight},
+    DataFile,
+};
+
+use crate::batch::{interpreter, prepare};
+
+const STYLE: &str = "body { margin: 0 auto; padding: 16px; max-width: 1100px; font-family: sans-serif; background: #f4f1ec; }
+a { color: #7d3e2f; }
+.grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(320px, 1fr)); gap: 16px; }
+.card { background: #fff; padding: 8px; }
+.card img, .card video { width: 100%; display: block; background: #000; }
+.card h2 { font-size: 16px; margin: 8px 0 4px; }
+.links a { margin-right: 12px; font-size: 14px; }";
+
+const SHEET_COLUMNS: u32 = 6;
+const SHEET_EVERY: f64 = 30.0;
+
+struct Chapter {
+    name: String,
+    title: String,
+    video: bool,
+    player: bool,
+    pgn: bool,
+    thumb: bool,
+    sheet: bool,
+}
+
+// static pages over the output tree, so the archive can be opened from disk or any static host
+pub fn write(src: &Path, out: &Path, every: Option<f64>) -> io::Result<()> {
+    let mut courses = sorted(src)?
+        .into_iter()
+        .map(|course| {
+            let name = course.file_name().unwrap().to_string_lossy().into_owned();
+            let out = out.join(&name);
+            fs::create_dir_all(&out)?;
+            let chapters = sorted(&course)?
+                .par_iter()
+                .map(|chapter| self::chapter(chapter, &out, every))
+                .collect::<Vec<_>>();
+            fs::write(out.join("index.html"), course_page(&name, &chapters))?;
+            Ok((name, chapters))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    courses.retain(|(_, chapters)| !chapters.is_empty());
+    fs::write(out.join("index.html"), index_page(&courses))
+}
+
+// `--contact-sheet[=SECONDS]`, how often the sheet takes a board
+pub fn sheet_every() -> Option<f64> {
+    env::args().find_map(|a| match a.strip_prefix("--contact-sheet")? {
+        "" => Some(SHEET_EVERY),
+        x => x.strip_prefix('=')?.parse().ok().filter(|&x: &f64| x > 0.0),
+    })
+}
+
+// the tutor's video at `t`, when there's an ffmpeg to pull it out with
+fn frame(video: &Path, t: f64) -> Option<RgbaImage> {
+    #[cfg(not(disable_ffmpeg))]
+    if video.exists()
+        && preflight()
+            .and_then(|t| t.require_encoders(&["png"]))
+            .is_ok()
+    {
+        return renderer::video::frame(video, t);
+    }
+    None
+}
+
+// pgn, poster and contact sheet of a chapter next to its video, whether it was rendered this run or
+// not, from the data as the video was rendered from it
+fn chapter(dir: &Path, out: &Path, every: Option<f64>) -> Chapter {
+    let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+    let data = File::open(dir.join("0.json"))
+        .ok()
+        .and_then(|f| serde_json::from_reader::<_, DataFile>(BufReader::new(f)).ok())
+        .map(|mut data| {
+            prepare(&mut data);
+            data
+        });
+    let title = data
+        .as_ref()
+        .and_then(|d| d.metadata.title.clone())
+        .unwrap_or_else(|| name.clone());
+    let pgn = out.join(format!("{name}.pgn"));
+    let thumb = out.join(format!("{name}.png"));
+    let sheet = out.join(format!("{name}.sheet.png"));
+    if let Some(data) = data {
+        fs::write(&pgn, pgn::export(&data, &title)).unwrap();
+        if !thumb.exists() || every.is_some() && !sheet.exists() {
+            let video = dir.join("video.webm");
+            let mut intrp = interpreter(data);
+            let snapshots = intrp.snapshots().0;
+            if let Some((t, d_t, _)) = thumb::representative(&snapshots).filter(|_| !thumb.exists())
+            {
+                let t = t + d_t / 2.0;
+                if let Some(board) = intrp.frame_at(t) {
+                    thumb::poster(&board, frame(&video, t).as_ref())
+                        .save(&thumb)
+                        .unwrap();
+                }
+            }
+            if let Some(every) = every.filter(|_| !sheet.exists()) {
+                let end = (ffprobe().is_ok())
+                    .then(|| duration(&video))
+                    .flatten()
+                    .or_else(|| snapshots.last().map(|(t, d_t, _)| t + d_t))
+                    .unwrap_or(0.0);
+                let boards = (0..)
+                    .map(|i| f64::from(i) * every)
+                    .take_while(|&t| t < end)
+                    .filter_map(|t| intrp.frame_at(t))
+                    .collect::<Vec<_>>();
+                if let Some(img) = thumb::contact_sheet(&boards, SHEET_COLUMNS) {
+                    img.save(&sheet).unwrap();
+                }
+            }
+        }
+    }
+    Chapter {
+        video: out.join(format!("{name}.mp4")).exists(),
+        player: out.join(format!("{name}.html")).exists(),
+        pgn: pgn.exists(),
+        thumb: thumb.exists(),
+        sheet: sheet.exists(),
+        name,
+        title,
+    }
+}
+
+fn index_page(courses: &[(String, Vec<Chapter>)]) -> String {
+    let cards = courses
+        .iter()
+        .map(|(name, chapters)| {
+            let course = href(name);
+            let thumb = chapters
+                .iter()
+                .find(|c| c.thumb)
+                .map(|c| format!("<img src=\"{course}/{}.png\" loading=\"lazy\">", href(&c.name)))
+                .unwrap_or_default();
+            format!(
+                "<div class=\"card\"><a href=\"{course}/index.html\">{thumb}<h2>{}</h2></a>{} chapters</div>",
+                escape(name),
+                chapters.len()
+            )
+        })
+        .collect::<String>();
+    page("Courses", "", &cards)
+}
+
+fn course_page(course: &str, chapters: &[Chapter]) -> String {
+    let cards = chapters
+        .iter()
+        .map(|c| {
+            let name = href(&c.name);
+            let poster = if c.thumb {
+                format!(" poster=\"{name}.png\"")
+            } else {
+                String::new()
+            };
+            let media = if c.video {
+                format!("<video src=\"{name}.mp4\"{poster} controls preload=\"none\"></video>")
+            } else if c.thumb {
+                format!("<img src=\"{name}.png\" loading=\"lazy\">")
+            } else {
+                String::new()
+            };
+            let mut links = String::new();
+            if c.player {
+                links.push_str(&format!("<a href=\"{name}.html\">interactive board</a>"));
+            }
+            if c.pgn {
+                links.push_str(&format!("<a href=\"{name}.pgn\" download>PGN</a>"));
+            }
+            if c.sheet {
+                links.push_str(&format!("<a href=\"{name}.sheet.png\">contact sheet</a>"));
+            }
+            format!(
+                "<div class=\"card\">{media}<h2>{}</h2><div class=\"links\">{links}</div></div>",
+                escape(&c.title)
+            )
+        })
+        .collect::<String>();
+    page(
+        course,
+        "<p><a href=\"../index.html\">all courses</a></p>",
+        &cards,
+    )
+}
+
+fn page(title: &str, nav: &str, cards: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n{nav}<h1>{title}</h1>\n<div class=\"grid\">{cards}</div>\n</body>\n</html>\n",
+        title = escape(title)
+    )
+}
+
+// directories only, chapters ("10. foo") in the order the scraper numbered them
+pub fn sorted(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let error = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", dir.display()));
+    let mut entries = fs::read_dir(dir)
+        .map_err(error)?
+        .map(|x| Ok(x.map_err(error)?.path()))
+        .filter(|x| x.as_ref().map_or(true, |x| x.is_dir()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_cached_key(|x| {
+        let name = x.file_name().unwrap().to_string_lossy().into_owned();
+        let n = name
+            .split('.')
+            .next()
+            .and_then(|n| n.trim().parse::<usize>().ok());
+        (n.unwrap_or(usize::MAX), name)
+    });
+    Ok(entries)
+}
@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use crate::instr::{DataFile, Instruction, InstructionData};
+
+// longest a subtitle stays up when nothing replaces it
+const MAX_SHOWN: f64 = 8.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+// the game's name when it's selected, the move in SAN whenever the board moves
+pub fn cues(data: &DataFile) -> Vec<Cue> {
+    let mut cues = data
+        .cuepoints
+        .iter()
+        .filter_map(|Instruction((t, instr))| {
+            let text = match *instr {
+                InstructionData::SelectGame { game_index, .. } => {
+                    let game = data.games.get(game_index)?;
+                    game.name
+                        .clone()
+                        .unwrap_or_else(|| format!("Game {}", game_index + 1))
+                }
+                InstructionData::Move { id, game_index, .. }
+                | InstructionData::GotoId { id, game_index } => {
+                    data.games.get(game_index)?.san(id)?
+                }
+                _ => return None,
+            };
+            Some(Cue { start: *t, end: t + MAX_SHOWN, text })
+        })
+        .collect::<Vec<_>>();
+    // a move and a jump to that same move right after say the same thing
+    cues.dedup_by(|b, a| a.text == b.text);
+    for i in 1..cues.len() {
+        cues[i - 1].end = cues[i - 1].end.min(cues[i].start);
+    }
+    cues.retain(|c| c.end > c.start);
+    cues
+}
+
+pub fn vtt(cues: &[Cue]) -> String {
+    let mut vtt = "WEBVTT\n".to_owned();
+    for c in cues {
+        let [start, end] = [c.start, c.end].map(|t| timestamp(t, '.'));
+        write!(vtt, "\n{start} --> {end}\n{}\n", c.text).unwrap();
+    }
+    vtt
+}
+
+pub fn srt(cues: &[Cue]) -> String {
+    let mut srt = String::new();
+    for (i, c) in cues.iter().enumerate() {
+        let [start, end] = [c.start, c.end].map(|t| timestamp(t, ','));
+        write!(srt, "{}\n{start} --> {end}\n{}\n\n", i + 1, c.text).unwrap();
+    }
+    srt
+}
+
+fn timestamp(t: f64, separator: char) -> String {
+    let ms = (t.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
@@ -0,0 +1,52 @@
+use image::{
+    imageops::{self, FilterType},
+    Rgba, RgbaImage,
+};
+
+use crate::{Chessboard, Snapshot};
+
+// the layout the videos are encoded with, see the filter in `video::SETTINGS`
+const CANVAS: [u32; 2] = [1080, 608];
+const BOARD: u32 = 588;
+const VIDEO: [u32; 2] = [461, 259];
+const MARGIN: u32 = 10;
+
+const CELL: u32 = Chessboard::BO_SIZE / 3;
+const GAP: u32 = 4;
+
+// the snapshot that stays on screen the longest
+pub fn representative(snapshots: &[(f64, f64, Snapshot)]) -> Option<&(f64, f64, Snapshot)> {
+    snapshots.iter().max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// a still of the rendered video at half size, `board` being the rendered board (or boards) and
+// `frame` the tutor's video at that moment
+pub fn poster(board: &RgbaImage, frame: Option<&RgbaImage>) -> RgbaImage {
+    let mut canvas = RgbaImage::from_pixel(CANVAS[0], CANVAS[1], Rgba([0xFF; 4]));
+    if let Some(frame) = frame {
+        let frame = imageops::resize(frame, VIDEO[0], VIDEO[1], FilterType::Triangle);
+        let x = CANVAS[0] - VIDEO[0] - MARGIN;
+        imageops::overlay(&mut canvas, &frame, x.into(), MARGIN.into());
+    }
+    let board = imageops::resize(board, BOARD, BOARD, FilterType::Triangle);
+    let y = CANVAS[1] - BOARD - MARGIN;
+    imageops::overlay(&mut canvas, &board, MARGIN.into(), y.into());
+    imageops::resize(&canvas, CANVAS[0] / 2, CANVAS[1] / 2, FilterType::Triangle)
+}
+
+// rendered boards in a grid, `columns` of them per row
+pub fn contact_sheet(boards: &[RgbaImage], columns: u32) -> Option<RgbaImage> {
+    if boards.is_empty() {
+        return None;
+    }
+    let columns = columns.min(boards.len() as u32);
+    let rows = (boards.len() as u32).div_ceil(columns);
+    let size = |n: u32| n * (CELL + GAP) + GAP;
+    let mut sheet = RgbaImage::from_pixel(size(columns), size(rows), Rgba([0xFF; 4]));
+    for (i, board) in (0..).zip(boards) {
+        let cell = imageops::resize(board, CELL, CELL, FilterType::Triangle);
+        let [x, y] = [i % columns, i / columns].map(|n| size(n).into());
+        imageops::overlay(&mut sheet, &cell, x, y);
+    }
+    Some(sheet)
+}
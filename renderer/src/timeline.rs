@@ -0,0 +1,156 @@
+use image::Rgba;
+use serde::Serialize;
+
+use crate::{
+    instr::{DataFile, Glyph, MoveData},
+    Interpreter,
+};
+
+// schema documented in TIMELINE.md, bump `VERSION` on breaking changes
+#[derive(Debug, Serialize)]
+pub struct Timeline {
+    pub version: u32,
+    pub cuepoints: Vec<Cue>,
+    pub games: Vec<GameTree>,
+    pub truncated_at: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Cue {
+    pub time: f64,
+    pub duration: f64,
+    pub game: usize,
+    pub move_id: usize,
+    pub fen: String,
+    pub last_move: Option<[String; 2]>,
+    pub arrows: Vec<Arrow>,
+    pub highlights: Vec<Highlight>,
+    pub circles: Vec<Highlight>,
+    pub fills: Vec<Highlight>,
+    pub glyphs: Vec<GlyphMark>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Arrow {
+    pub from: String,
+    pub to: String,
+    pub color: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Highlight {
+    pub square: String,
+    pub color: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlyphMark {
+    pub square: String,
+    pub glyph: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameTree {
+    pub moves: Vec<MoveNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveNode {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub ply: usize,
+    pub label: String,
+    pub san: Option<String>,
+    pub glyph: Option<&'static str>,
+    pub fen: String,
+}
+
+impl Timeline {
+    // bumped on breaking changes only, fields can be added without (see TIMELINE.md)
+    pub const VERSION: u32 = 1;
+
+    pub fn new(data: DataFile) -> Self {
+        let games = data.games.iter().map(GameTree::new).collect();
+        let (snapshots, truncated_at) = Interpreter::new(data).snapshots();
+        let cuepoints = snapshots
+            .into_iter()
+            .map(|(time, duration, s)| {
+                let mut arrows = s
+                    .board
+                    .arrows()
+                    .map(|([from, to], c)| Arrow {
+                        from: square(from),
+                        to: square(to),
+                        color: hex(c),
+                    })
+                    .collect::<Vec<_>>();
+                let squares = |marks: &mut dyn Iterator<Item = ([u32; 2], Rgba<u8>)>| {
+                    let mut marks = marks
+                        .map(|(sq, c)| Highlight { square: square(sq), color: hex(c) })
+                        .collect::<Vec<_>>();
+                    marks.sort_unstable_by(|a, b| a.square.cmp(&b.square));
+                    marks
+                };
+                let mut glyphs = s
+                    .board
+                    .glyphs()
+                    .map(|(sq, g)| GlyphMark { square: square(sq), glyph: g.as_str() })
+                    .collect::<Vec<_>>();
+                // hashmap order, keep the output stable
+                arrows.sort_unstable_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+                glyphs.sort_unstable_by(|a, b| a.square.cmp(&b.square));
+                Cue {
+                    time,
+                    duration,
+                    game: s.game_index,
+                    move_id: s.move_id,
+                    fen: s.board.fen(),
+                    last_move: s.board.last_move().map(|m| m.map(square)),
+                    arrows,
+                    highlights: squares(&mut s.board.highlts()),
+                    circles: squares(&mut s.board.circles()),
+                    fills: squares(&mut s.board.fills()),
+                    glyphs,
+                }
+            })
+            .collect();
+        Self { version: Self::VERSION, cuepoints, games, truncated_at }
+    }
+}
+
+impl GameTree {
+    fn new(game: &crate::Game) -> Self {
+        let mut moves = game
+            .moves
+            .iter()
+            .map(|(&id, m)| {
+                let line = game.line(id);
+                MoveNode {
+                    id,
+                    parent: game.parent(id),
+                    ply: line.len(),
+                    label: match &m.data {
+                        MoveData::Coord(([from, to], promotion)) => format!(
+                            "{}{}{}",
+                            square(*from),
+                            square(*to),
+                            promotion.map_or("", |p| &"pnbrqk"[p..=p])
+                        ),
+                        MoveData::Fen(_) => "…".to_owned(),
+                    },
+                    san: game.san(id),
+                    glyph: m.glyph.map(Glyph::as_str),
+                    fen: game.position(id).fen(),
+                }
+            })
+            .collect::<Vec<_>>();
+        moves.sort_unstable_by_key(|m| m.id);
+        Self { moves }
+    }
+}
+
+pub fn square([file, rank]: [u32; 2]) -> String {
+    format!("{}{rank}", char::from(b'a' + file as u8 - 1))
+}
+
+fn hex(Rgba([r, g, b, a]): Rgba<u8>) -> String { format!("#{r:02x}{g:02x}{b:02x}{a:02x}") }
@@ -0,0 +1,117 @@
+use std::{fs::File, io::BufReader, path::Path, process};
+
+use rayon::prelude::*;
+use renderer::{
+    check::check,
+    video::{duration, ffprobe},
+    DataFile,
+};
+
+use crate::site::sorted;
+
+struct Row {
+    name: String,
+    cuepoints: Option<usize>,
+    games: Option<usize>,
+    end: Option<f64>,
+    problems: Vec<String>,
+}
+
+// lints every chapter under `src` without rendering anything, a table of them and then what's
+// wrong with each, exiting with 1 if anything is
+pub fn run(src: &Path) {
+    let probe = ffprobe().is_ok();
+    if !probe {
+        eprintln!("no ffprobe, cuepoints aren't checked against the videos' length");
+    }
+    let chapters = sorted(src)
+        .and_then(|courses| {
+            courses
+                .iter()
+                .map(|c| sorted(c))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        });
+    let rows = chapters
+        .concat()
+        .par_iter()
+        .map(|dir| chapter(src, dir, probe))
+        .collect::<Vec<_>>();
+
+    let width = rows.iter().map(|r| r.name.len()).max().unwrap_or(0).max(7);
+    let n = |x: Option<usize>| x.map_or_else(|| "-".to_owned(), |x| x.to_string());
+    println!(
+        "{:width$}  {:>9}  {:>5}  {:>8}  {:>8}",
+        "chapter", "cuepoints", "games", "length", "problems"
+    );
+    for r in &rows {
+        println!(
+            "{:width$}  {:>9}  {:>5}  {:>8}  {:>8}",
+            r.name,
+            n(r.cuepoints),
+            n(r.games),
+            r.end.map_or_else(|| "-".to_owned(), |x| format!("{x:.1}s")),
+            r.problems.len()
+        );
+    }
+    let broken = rows
+        .iter()
+        .filter(|r| !r.problems.is_empty())
+        .collect::<Vec<_>>();
+    for r in &broken {
+        println!("\n{}", r.name);
+        for p in &r.problems {
+            println!("  {p}");
+        }
+    }
+    println!("\n{} chapters, {} with problems", rows.len(), broken.len());
+    if !broken.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn chapter(src: &Path, dir: &Path, probe: bool) -> Row {
+    let name = dir
+        .strip_prefix(src)
+        .unwrap_or(dir)
+        .to_string_lossy()
+        .into_owned();
+    let mut problems = vec![];
+    let video = dir.join("video.webm");
+    let end = if !video.exists() {
+        problems.push("video.webm is missing".to_owned());
+        None
+    } else if probe {
+        let end = duration(&video);
+        if end.is_none() {
+            problems.push("video.webm is unreadable".to_owned());
+        }
+        end
+    } else {
+        None
+    };
+    let data = File::open(dir.join("0.json"))
+        .map_err(|e| e.to_string())
+        .and_then(|f| {
+            serde_json::from_reader::<_, DataFile>(BufReader::new(f)).map_err(|e| e.to_string())
+        });
+    match data {
+        Ok(data) => {
+            problems.extend(check(&data, end).iter().map(ToString::to_string));
+            Row {
+                name,
+                cuepoints: Some(data.cuepoints.len()),
+                games: Some(data.games.len()),
+                end,
+                problems,
+            }
+        }
+        Err(e) => {
+            problems.push(format!("0.json: {e}"));
+            Row { name, cuepoints: None, games: None, end, problems }
+        }
+    }
+}
@@ -1,26 +1,156 @@
 use std::{
-    fs,
+    env, fs,
     path::{Path, PathBuf},
     process::Command,
 };
 
-use crossbeam::channel::Receiver;
 #[cfg(not(disable_ffmpeg))] pub use ffmpeg::*;
+use once_cell::sync::OnceCell;
 
 use crate::str;
 
-#[cfg(not(disable_ffmpeg))]
-mod ffmpeg {
-    use super::*;
+pub const SETTINGS: [&str; 8] = [
+    "-c:a", "copy",
+    "-c:v", "h264",
+    "-pix_fmt", "yuv420p",
+    "-filter_complex", "[1:v]scale=460.8:259.2[top_right];[0:v]scale=588:588[left];color=white:1080x608[bg];[bg][top_right]overlay=W-w-10:10[bg1];[bg1][left]overlay=10:H-h-10",
+];
+
+const PARTIAL: &str = ".part.mp4";
+
+static TOOLS: OnceCell<Tools> = OnceCell::new();
+static FFPROBE: OnceCell<Tool> = OnceCell::new();
+
+#[derive(Debug)]
+pub struct Tools {
+    #[cfg(not(disable_ffmpeg))]
+    pub ffmpeg: Tool,
+    pub ffprobe: &'static Tool,
+}
+
+#[derive(Debug)]
+pub struct Tool {
+    pub path: PathBuf,
+    pub version: String,
+}
+
+impl Tools {
+    #[cfg(not(disable_ffmpeg))]
+    const ENCODERS: [&'static str; 1] = ["h264"];
+    #[cfg(not(disable_ffmpeg))]
+    const FILTERS: [&'static str; 3] = ["scale", "overlay", "color"];
+    const MIN_MAJOR: u32 = 4;
+
+    fn locate() -> Result<Self, String> {
+        Ok(Self {
+            #[cfg(not(disable_ffmpeg))]
+            ffmpeg: {
+                let ffmpeg = Tool::locate("ffmpeg")?;
+                ffmpeg.require("demuxer", "concat")?;
+                for e in Self::ENCODERS {
+                    ffmpeg.require("encoder", e)?;
+                }
+                for f in Self::FILTERS {
+                    ffmpeg.require("filter", f)?;
+                }
+                ffmpeg
+            },
+            ffprobe: ffprobe()?,
+        })
+    }
+
+    // encoders only some outputs need, checked when they're asked for
+    #[cfg(not(disable_ffmpeg))]
+    pub fn require_encoders(&self, names: &[&str]) -> Result<(), String> {
+        names
+            .iter()
+            .try_for_each(|e| self.ffmpeg.require("encoder", e))
+    }
+}
 
-    pub fn handle_ffmpeg(rx: &Receiver<([PathBuf; 3], String, Option<f64>)>) {
-        while let Ok(([video, concat, mut out], tmp, t)) = rx.recv() {
-            ffmpeg_join(&video, &concat, &out, t);
-            out.pop();
-            out.push(tmp);
-            fs::remove_dir_all(&out).unwrap();
+impl Tool {
+    fn locate(name: &str) -> Result<Self, String> {
+        let var = name.to_ascii_uppercase();
+        let path = match env::var_os(&var) {
+            Some(p) => PathBuf::from(p),
+            None => env::var_os("PATH")
+                .iter()
+                .flat_map(env::split_paths)
+                .map(|d| d.join(name))
+                .find(|p| p.is_file())
+                .ok_or_else(|| format!("{name} not found in PATH (set {var} to override)"))?,
+        };
+        let out = Command::new(&path)
+            .args(["-hide_banner", "-version"])
+            .output()
+            .map_err(|e| format!("could not run {}: {e}", path.display()))?;
+        let version = String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix(&format!("{name} version ")))
+            .and_then(|l| l.split_whitespace().next())
+            .ok_or_else(|| format!("{} does not look like {name}", path.display()))?
+            .to_owned();
+        // git builds report something like `N-113414-g...`, assume those are recent enough
+        let major = version
+            .trim_start_matches('n')
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .and_then(|m| m.parse::<u32>().ok());
+        if major.is_some_and(|m| m < Tools::MIN_MAJOR) {
+            return Err(format!(
+                "{name} {version} is too old, at least {}.0 is required",
+                Tools::MIN_MAJOR
+            ));
         }
+        Ok(Self { path, version })
+    }
+
+    #[cfg(not(disable_ffmpeg))]
+    fn require(&self, kind: &str, name: &str) -> Result<(), String> {
+        let out = Command::new(&self.path)
+            .args(["-hide_banner", "-h", &format!("{kind}={name}")])
+            .output()
+            .map_err(|e| format!("could not run {}: {e}", self.path.display()))?;
+        let mut kind_c = kind.to_owned();
+        kind_c[..1].make_ascii_uppercase();
+        if String::from_utf8_lossy(&out.stdout).starts_with(&kind_c) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} {} lacks the {name} {kind}",
+                self.path.display(),
+                self.version
+            ))
+        }
+    }
+}
+
+pub fn preflight() -> Result<&'static Tools, String> { TOOLS.get_or_try_init(Tools::locate) }
+
+pub fn tools() -> &'static Tools { preflight().unwrap_or_else(|e| panic!("{e}")) }
+
+// what soft subtitles are muxed into `video` with
+pub fn subtitle_encoder(video: &Path) -> &'static str {
+    match video.extension().and_then(|x| x.to_str()) {
+        Some("mkv") => "srt",
+        _ => "mov_text",
     }
+}
+
+// just ffprobe, for what only needs videos' lengths
+pub fn ffprobe() -> Result<&'static Tool, String> {
+    FFPROBE.get_or_try_init(|| Tool::locate("ffprobe"))
+}
+
+#[cfg(not(disable_ffmpeg))]
+mod ffmpeg {
+    use std::{thread, time::Duration};
+
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::cancelled;
 
     pub fn ffmpeg_join(
         video: impl AsRef<Path>,
@@ -28,29 +158,140 @@ mod ffmpeg {
         out: impl AsRef<Path>,
         custom_t: Option<f64>,
     ) -> i32 {
-        Command::new("ffmpeg")
+        let out = out.as_ref();
+        let part = partial(out);
+        #[rustfmt::skip]
+        let child = Command::new(&tools().ffmpeg.path)
         .args([
             "-nostdin",
             "-f", "concat",
             "-safe", "0",
             "-i", str(concat.as_ref()),
             "-i", str(video.as_ref()),
-            "-c:a", "copy",
-            "-c:v", "h264",
-            "-pix_fmt", "yuv420p",
-            "-filter_complex", "[1:v]scale=460.8:259.2[top_right];[0:v]scale=588:588[left];color=white:1080x608[bg];[bg][top_right]overlay=W-w-10:10[bg1];[bg1][left]overlay=10:H-h-10",
+        ]).args(SETTINGS).args([
             "-y",
             "-loglevel", "error",
             "-threads", "0",
             "-t", &custom_t.or_else(|| duration(video.as_ref())).unwrap().to_string(),
-            str(out.as_ref())
-        ]).status().ok().and_then(|x| x.code()).unwrap_or(-1)
+            str(&part)
+        ]).spawn();
+        let code = match child {
+            Ok(mut child) => loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break status.code().unwrap_or(-1),
+                    Ok(None) if cancelled() => {
+                        child.kill().unwrap_or(());
+                        child.wait().ok();
+                        break -1;
+                    }
+                    Ok(None) => thread::sleep(Duration::from_millis(100)),
+                    Err(_) => break -1,
+                }
+            },
+            Err(_) => -1,
+        };
+        if code == 0 {
+            fs::rename(&part, out).unwrap();
+        } else {
+            fs::remove_file(&part).unwrap_or(());
+        }
+        code
+    }
+
+    // adds a soft subtitle track and/or ffmetadata (chapter markers, title) to an MP4 or MKV in
+    // place, whatever it already carries stays
+    pub fn ffmpeg_remux(video: impl AsRef<Path>, subs: Option<&Path>, meta: Option<&Path>) -> i32 {
+        let video = video.as_ref();
+        let ext = video.extension().and_then(|x| x.to_str()).unwrap_or("mp4");
+        let part = video.with_extension(format!("part.{ext}"));
+        let mut cmd = Command::new(&tools().ffmpeg.path);
+        cmd.args(["-nostdin", "-loglevel", "error", "-i", str(video)]);
+        let mut maps = vec!["-map".to_owned(), "0".to_owned()];
+        if let Some(subs) = subs {
+            cmd.args(["-i", str(subs)]);
+            maps.extend(["-map".to_owned(), "1".to_owned()]);
+        }
+        if let Some(meta) = meta {
+            cmd.args(["-f", "ffmetadata", "-i", str(meta)]);
+            let i = (1 + usize::from(subs.is_some())).to_string();
+            maps.extend(["-map_metadata".to_owned(), i.clone(), "-map_chapters".to_owned(), i]);
+        }
+        #[rustfmt::skip]
+        let status = cmd
+            .args(maps)
+            .args([
+                "-c", "copy",
+                "-c:s", subtitle_encoder(video),
+                "-y", str(&part),
+            ])
+            .status();
+        let code = status.ok().and_then(|s| s.code()).unwrap_or(-1);
+        if code == 0 {
+            fs::rename(&part, video).unwrap();
+        } else {
+            fs::remove_file(&part).unwrap_or(());
+        }
+        code
+    }
+
+    // concat list of PNGs (see `DirSink`) into a looping animation, `args` picking the format
+    pub fn ffmpeg_animate(concat: impl AsRef<Path>, out: impl AsRef<Path>, args: &[&str]) -> i32 {
+        #[rustfmt::skip]
+        let status = Command::new(&tools().ffmpeg.path)
+            .args([
+                "-nostdin",
+                "-loglevel", "error",
+                "-f", "concat",
+                "-safe", "0",
+                "-i", str(concat.as_ref()),
+                "-vsync", "vfr",
+            ])
+            .args(args)
+            .args(["-y", str(out.as_ref())])
+            .status();
+        status.ok().and_then(|s| s.code()).unwrap_or(-1)
+    }
+
+    // still of `video` at `t`
+    pub fn frame(video: impl AsRef<Path>, t: f64) -> Option<RgbaImage> {
+        #[rustfmt::skip]
+        let out = Command::new(&tools().ffmpeg.path)
+            .args([
+                "-nostdin",
+                "-loglevel", "error",
+                "-ss", &t.to_string(),
+                "-i", str(video.as_ref()),
+                "-frames:v", "1",
+                "-f", "image2pipe",
+                "-c:v", "png",
+                "-",
+            ])
+            .output()
+            .ok()?;
+        Some(image::load_from_memory(&out.stdout).ok()?.to_rgba8())
+    }
+
+    fn partial(out: &Path) -> PathBuf {
+        let mut name = out.file_stem().unwrap().to_owned();
+        name.push(PARTIAL);
+        out.with_file_name(name)
+    }
+}
+
+pub fn clean_partials(out: &Path) {
+    for entry in fs::read_dir(out).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            clean_partials(&path);
+        } else if path.to_string_lossy().ends_with(PARTIAL) {
+            fs::remove_file(&path).unwrap_or(());
+        }
     }
 }
 
 pub fn duration(path: impl AsRef<Path>) -> Option<f64> {
     String::from_utf8_lossy(
-        &Command::new("ffprobe")
+        &Command::new(&ffprobe().ok()?.path)
             .args([
                 "-v",
                 "error",
@@ -0,0 +1,122 @@
+mod common;
+
+use common::fixture;
+use renderer::{
+    check::{check, repair, Fix, Problem, Repair},
+    DataFile, Interpreter,
+};
+use serde_json::json;
+
+#[test]
+fn clean() {
+    assert_eq!(check(&fixture("every_cuepoint"), Some(21.0)), []);
+    assert_eq!(check(&fixture("every_cuepoint"), None), []);
+}
+
+#[test]
+fn past_the_video() {
+    assert_eq!(
+        check(&fixture("every_cuepoint"), Some(19.0)),
+        [Problem::PastEnd { t: 19.75, end: 19.0 }]
+    );
+}
+
+#[test]
+fn missing_game() {
+    assert_eq!(
+        check(&fixture("missing_game"), None),
+        [Problem::NoGame { t: 3.0, game_index: 4 }]
+    );
+}
+
+fn broken() -> DataFile {
+    serde_json::from_value::<DataFile>(json!({
+        "games": [{
+            "video_start_fen": "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "moves": [
+                { "id": 0, "pm": -1, "m": "e2e4" },
+                { "id": 1, "pm": 0, "m": "e7e5" },
+                // follows a move that isn't there, and so does the one after
+                { "id": 2, "pm": 7, "m": "g1f3" },
+                { "id": 3, "pm": 2, "m": "b8c6" },
+                // going round in circles
+                { "id": 4, "pm": 5, "m": "d2d4" },
+                { "id": 5, "pm": 4, "m": "d7d5" },
+            ],
+        }],
+        "cuepoints": [
+            { "name": "unarrowAll", "time": 0, "data": { "gameIndex": 0 } },
+            { "name": "selectGame", "time": 1, "data": { "gameIndex": 0, "initialMoveId": 0 } },
+            { "name": "gotoId", "time": 3, "data": { "gameIndex": 0, "id": 9 } },
+            { "name": "unarrowAll", "time": 2, "data": { "gameIndex": 0 } },
+            { "name": "gotoId", "time": 4, "data": { "gameIndex": 1, "id": 1 } },
+        ],
+    }))
+    .unwrap()
+}
+
+#[test]
+fn broken_moves_and_cuepoints() {
+    assert_eq!(
+        check(&broken(), None),
+        [
+            Problem::Dangling { game_index: 0, id: 2, pm: 7 },
+            Problem::Loop { game_index: 0, id: 4 },
+            Problem::NoBoard { t: 0.0, game_index: 0 },
+            Problem::NoMove { t: 3.0, game_index: 0, id: 9 },
+            Problem::OutOfOrder { t: 2.0, prev: 3.0 },
+            Problem::NoGame { t: 4.0, game_index: 1 },
+        ]
+    );
+}
+
+#[test]
+fn repairs() {
+    let mut data = broken();
+    let fixes = repair(&mut data)
+        .into_iter()
+        .map(|Repair { problem, fix }| (problem, fix))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fixes,
+        [
+            // 1. e4 e5 2. Nf3, and 3. d4 after 2... Nc6
+            (
+                Problem::Dangling { game_index: 0, id: 2, pm: 7 },
+                Fix::Reattached { pm: 1 }
+            ),
+            (
+                Problem::Loop { game_index: 0, id: 4 },
+                Fix::Reattached { pm: 3 }
+            ),
+            (Problem::OutOfOrder { t: 2.0, prev: 3.0 }, Fix::Sorted),
+            (Problem::NoBoard { t: 0.0, game_index: 0 }, Fix::Skipped),
+            (
+                Problem::NoMove { t: 3.0, game_index: 0, id: 9 },
+                Fix::Skipped
+            ),
+            (Problem::NoGame { t: 4.0, game_index: 1 }, Fix::Skipped),
+        ]
+    );
+    assert_eq!(check(&data, None), []);
+    assert_eq!(data.games[0].line(5), [0, 1, 2, 3, 4, 5]);
+    // nothing left to repair
+    assert_eq!(repair(&mut data), []);
+}
+
+#[test]
+fn repair_keeps_the_rest_of_the_video() {
+    let mut data = fixture("missing_game");
+    repair(&mut data);
+    let (snapshots, truncated) = Interpreter::new(data).snapshots();
+    assert_eq!(truncated, None);
+    let frames = snapshots
+        .iter()
+        .map(|(t, d_t, s)| (*t, *d_t, [s.game_index, s.move_id]))
+        .collect::<Vec<_>>();
+    // the board stays put over the missing game
+    assert_eq!(
+        frames,
+        [(0.0, 1.5, [0, 0]), (1.5, 2.5, [0, 1]), (4.0, 1.0, [0, 2])]
+    );
+}
@@ -0,0 +1,11 @@
+// helpers shared by the integration tests
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use renderer::DataFile;
+
+// one of the hand-written datafiles in tests/fixtures/
+pub fn fixture(name: &str) -> DataFile {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("tests/fixtures/{name}.json"));
+    serde_json::from_reader(BufReader::new(File::open(path).unwrap())).unwrap()
+}
@@ -0,0 +1,232 @@
+// renders boards and compares them against the references in tests/golden/, differences a person
+// wouldn't notice (anti-aliasing, rounding) are tolerated
+//
+// `GOLDEN_UPDATE=1 cargo test --test golden` rewrites the references, on failures the rendered
+// board and a diff (red where it's off) are left in the target directory
+use std::{env, path::PathBuf};
+
+use image::{Rgba, RgbaImage};
+use palette::{FromColor, Lab, Srgb};
+use renderer::{
+    instr::{Fen, Glyph},
+    Chessboard,
+};
+
+// CIE76 distance under which two colors look the same
+const MAX_DELTA_E: f32 = 2.3;
+// share of pixels allowed to look different at all
+const MAX_DIFFERING: f64 = 0.001;
+
+const GREEN: Rgba<u8> = Rgba([0x27, 0xDB, 0x33, 0xFF]);
+const RED: Rgba<u8> = Rgba([0xDB, 0x33, 0x28, 0xFF]);
+const BLUE: Rgba<u8> = Rgba([0x33, 0x27, 0xDB, 0xFF]);
+const YELLOW: Rgba<u8> = Rgba([0xDB, 0xDB, 0x00, 0xFF]);
+
+fn lab(Rgba([r, g, b, _]): Rgba<u8>) -> Lab {
+    Lab::from_color(Srgb::new(r, g, b).into_format::<f32>().into_linear())
+}
+
+fn check(name: &str, board: &Chessboard) { check_image(name, board.render()) }
+
+fn check_image(name: &str, actual: RgbaImage) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let reference = dir.join(format!("{name}.png"));
+    if env::var_os("GOLDEN_UPDATE").is_some() {
+        actual.save(&reference).unwrap();
+        return;
+    }
+    let expected = image::open(&reference)
+        .unwrap_or_else(|e| {
+            panic!(
+                "{}: {e} (GOLDEN_UPDATE=1 to create it)",
+                reference.display()
+            )
+        })
+        .to_rgba8();
+    assert_eq!(actual.dimensions(), expected.dimensions(), "{name}: size");
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut differing = 0;
+    for ((a, e), d) in actual
+        .pixels()
+        .zip(expected.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let [l_a, l_e] = [*a, *e].map(lab);
+        let delta =
+            ((l_a.l - l_e.l).powi(2) + (l_a.a - l_e.a).powi(2) + (l_a.b - l_e.b).powi(2)).sqrt();
+        *d = if delta > MAX_DELTA_E || a[3].abs_diff(e[3]) > 2 {
+            differing += 1;
+            Rgba([0xFF, 0, 0, 0xFF])
+        } else {
+            // the reference, faded, to see where things are
+            let v = ((u16::from(e[0]) + u16::from(e[1]) + u16::from(e[2])) / 3 / 4 + 0xC0) as u8;
+            Rgba([v, v, v, 0xFF])
+        };
+    }
+    let share = f64::from(differing) / f64::from(actual.width() * actual.height());
+    if share > MAX_DIFFERING {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&out).unwrap();
+        actual.save(out.join(format!("{name}.png"))).unwrap();
+        diff.save(out.join(format!("{name}.diff.png"))).unwrap();
+        panic!(
+            "{name}: {differing} pixels ({:.3}%) differ from the reference, see {}",
+            share * 100.0,
+            out.display()
+        );
+    }
+}
+
+fn board(fen: &str) -> Chessboard {
+    let mut board = Chessboard::new();
+    board.set_fen(&Fen::new(fen.to_owned()));
+    board
+}
+
+#[test]
+fn empty() { check("empty", &Chessboard::new()) }
+
+#[test]
+fn initial_position() {
+    check(
+        "initial",
+        &board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    )
+}
+
+#[test]
+fn highlights() {
+    let mut board = board("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+    // corners, edges and a square under a piece, in every color
+    for (sq, color) in [
+        ([1, 1], YELLOW),
+        ([8, 8], GREEN),
+        ([1, 8], BLUE),
+        ([8, 1], RED),
+        ([4, 1], GREEN),
+        ([6, 7], RED),
+        ([5, 5], YELLOW),
+        ([3, 4], BLUE),
+    ] {
+        board.highlt(sq, color);
+    }
+    check("highlights", &board);
+}
+
+#[test]
+fn arrows_straight() {
+    // the eight directions out of d4, one and several squares long
+    let mut board = Chessboard::new();
+    for (to, color) in [
+        ([4, 8], GREEN),
+        ([8, 8], RED),
+        ([8, 4], BLUE),
+        ([7, 1], YELLOW),
+        ([4, 3], GREEN),
+        ([3, 3], RED),
+        ([1, 4], BLUE),
+        ([3, 5], YELLOW),
+    ] {
+        board.arrow([[4, 4], to], color);
+    }
+    check("arrows_straight", &board);
+}
+
+#[test]
+fn arrows_knight() {
+    // every knight jump out of e5, plus the ones hugging the edges of the board
+    let mut board = Chessboard::new();
+    for d in [[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2]] {
+        board.arrow([[5, 5], [(5 + d[0]) as u32, (5 + d[1]) as u32]], GREEN);
+    }
+    board.arrow([[1, 1], [2, 3]], RED);
+    board.arrow([[8, 8], [6, 7]], BLUE);
+    board.arrow([[8, 1], [7, 3]], YELLOW);
+    board.arrow([[1, 8], [3, 7]], RED);
+    check("arrows_knight", &board);
+}
+
+#[test]
+fn arrows_edges() {
+    // along the edges and across the whole board both ways
+    let mut board = Chessboard::new();
+    board.arrow([[1, 1], [8, 8]], GREEN);
+    board.arrow([[8, 1], [1, 8]], RED);
+    board.arrow([[1, 2], [1, 7]], BLUE);
+    board.arrow([[2, 8], [7, 8]], YELLOW);
+    board.arrow([[8, 7], [8, 2]], BLUE);
+    board.arrow([[7, 1], [2, 1]], YELLOW);
+    check("arrows_edges", &board);
+}
+
+#[test]
+fn markers_over_pieces() {
+    let mut board = board("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4");
+    board.highlt([6, 7], RED);
+    board.highlt([8, 5], GREEN);
+    board.arrow([[8, 5], [6, 7]], GREEN);
+    board.arrow([[3, 4], [6, 7]], GREEN);
+    board.arrow([[6, 6], [8, 5]], RED);
+    check("markers_over_pieces", &board);
+}
+
+#[test]
+fn two_boards() {
+    let mut left = board("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+    left.arrow([[5, 7], [5, 5]], BLUE);
+    let mut right = board("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    right.highlt([1, 8], GREEN);
+    check_image("two_boards", Chessboard::render_pair(&left, &right));
+}
+
+#[test]
+fn last_move() {
+    // under a highlight on the square moved to and an arrow out of it
+    let mut board = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    board.set_last_move(Some([[2, 8], [3, 6]]));
+    board.highlt([3, 6], GREEN);
+    board.arrow([[6, 3], [5, 5]], RED);
+    check("last_move", &board);
+}
+
+#[test]
+fn in_check() {
+    // 3. Bb5+, the glow under the king and not the rest of the board
+    let mut board = board("rnbqkbnr/ppp2ppp/3p4/1B2p3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 3");
+    board.highlt([5, 8], GREEN);
+    check("check", &board);
+}
+
+#[test]
+fn game_over() {
+    // scholar's mate, and a queen and king stalemating the other king in the corner
+    let mate = board("r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4");
+    let stalemate = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    check("checkmate", &mate);
+    check("stalemate", &stalemate);
+}
+
+#[test]
+fn circles_fills_and_glyphs() {
+    let mut board = board("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+    board.set_last_move(Some([[7, 8], [6, 6]]));
+    // around pieces and on the edges, over the last move, and every glyph with one in a corner
+    board.circle([6, 6], GREEN);
+    board.circle([1, 1], RED);
+    board.circle([4, 4], BLUE);
+    board.fill([6, 6], RED);
+    board.fill([4, 5], YELLOW);
+    board.fill([8, 1], BLUE);
+    for (sq, glyph) in [
+        ([8, 8], Glyph::Good),
+        ([3, 4], Glyph::Mistake),
+        ([5, 4], Glyph::Brilliant),
+        ([1, 8], Glyph::Blunder),
+        ([6, 3], Glyph::Interesting),
+        ([5, 5], Glyph::Dubious),
+    ] {
+        board.glyph(sq, glyph);
+    }
+    check("circles_fills_glyphs", &board);
+}
@@ -0,0 +1,338 @@
+// runs the interpreter over the hand-written datafiles in tests/fixtures/ and checks what ends up
+// on screen at every cuepoint
+mod common;
+
+use common::fixture;
+use renderer::{timeline::square, Chessboard, Interpreter, MemorySink, Snapshot};
+
+fn arrows(board: &Chessboard) -> Vec<String> {
+    let mut arrows = board
+        .arrows()
+        .map(|([from, to], _)| square(from) + &square(to))
+        .collect::<Vec<_>>();
+    arrows.sort_unstable();
+    arrows
+}
+
+fn highlights(board: &Chessboard) -> Vec<String> {
+    let mut highlights = board
+        .highlts()
+        .map(|(sq, _)| square(sq))
+        .collect::<Vec<_>>();
+    highlights.sort_unstable();
+    highlights
+}
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const E4: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+const E5: &str = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2";
+const SICILIAN: &str = "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+const KING_KNIGHT: &str = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+const NC6: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+const ENDGAME: &str = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+const PROMOTED: &str = "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1";
+const KD7: &str = "Q7/3k4/8/8/8/8/8/4K3 w - - 1 2";
+
+// time, [game, move], FEN, last move, arrows, highlights
+type Expected = (
+    f64,
+    [usize; 2],
+    &'static str,
+    Option<&'static str>,
+    &'static [&'static str],
+    &'static [&'static str],
+);
+
+fn assert_states(fixture_name: &str, expected: &[Expected]) {
+    let (snapshots, truncated) = Interpreter::new(fixture(fixture_name)).snapshots();
+    assert_eq!(truncated, None);
+    assert_eq!(snapshots.len(), expected.len());
+    for ((t, _, s), (e_t, [game, id], fen, last_move, e_arrows, e_highlights)) in
+        snapshots.iter().zip(expected.iter().copied())
+    {
+        assert_eq!(*t, e_t);
+        assert_eq!([s.game_index, s.move_id], [game, id], "at {t}");
+        assert_eq!(s.board.fen(), fen, "at {t}");
+        assert_eq!(
+            s.board
+                .last_move()
+                .map(|[from, to]| square(from) + &square(to)),
+            last_move.map(str::to_owned),
+            "at {t}"
+        );
+        assert_eq!(arrows(&s.board), e_arrows, "at {t}");
+        assert_eq!(highlights(&s.board), e_highlights, "at {t}");
+    }
+}
+
+#[test]
+fn every_cuepoint() {
+    #[rustfmt::skip]
+    let expected: [Expected; 24] = [
+        // selectGame with initialMoveId
+        (0.0, [0, 0], START, None, &[], &[]),
+        // gotoId, drawArrow, highlightSquare, unarrow
+        (1.0, [0, 1], E4, Some("e2e4"), &[], &[]),
+        (2.0, [0, 1], E4, Some("e2e4"), &["g1f3"], &[]),
+        (3.0, [0, 1], E4, Some("e2e4"), &["g1f3"], &["e4"]),
+        (4.0, [0, 1], E4, Some("e2e4"), &[], &["e4"]),
+        // moving on clears the markers
+        (5.0, [0, 2], E5, Some("e7e5"), &[], &[]),
+        // over to another branch, through a move that was never shown
+        (6.0, [0, 5], SICILIAN, Some("g1f3"), &[], &[]),
+        (7.0, [0, 5], SICILIAN, Some("g1f3"), &[], &["c5"]),
+        (7.5, [0, 5], SICILIAN, Some("g1f3"), &[], &["c5", "f3"]),
+        // unmark, clearAllHighlights, unarrowAll, none of which touch the last move
+        (8.0, [0, 5], SICILIAN, Some("g1f3"), &[], &["f3"]),
+        (9.0, [0, 5], SICILIAN, Some("g1f3"), &[], &[]),
+        (10.0, [0, 5], SICILIAN, Some("g1f3"), &["d7d5"], &[]),
+        (10.5, [0, 5], SICILIAN, Some("g1f3"), &[], &[]),
+        // and back to the first one
+        (11.0, [0, 3], KING_KNIGHT, Some("g1f3"), &[], &[]),
+        // move, the last move coming from what changed on the board
+        (12.0, [0, 6], NC6, Some("b8c6"), &[], &[]),
+        (13.0, [0, 6], NC6, Some("b8c6"), &[], &["a1"]),
+        (13.25, [0, 6], NC6, Some("b8c6"), &["a1h8"], &["a1"]),
+        // unmarkAll
+        (13.5, [0, 6], NC6, Some("b8c6"), &[], &[]),
+        // triggerExerciseGroup does nothing
+        (14.0, [0, 6], NC6, Some("b8c6"), &[], &[]),
+        // second game, with a promotion
+        (15.0, [1, 0], ENDGAME, None, &[], &[]),
+        (16.0, [1, 1], PROMOTED, Some("a7a8"), &[], &[]),
+        (17.0, [1, 2], KD7, Some("e8d7"), &[], &[]),
+        // selectGame without initialMoveId goes back to where each game was left
+        (18.0, [0, 6], NC6, Some("b8c6"), &[], &[]),
+        (19.75, [1, 2], KD7, Some("e8d7"), &[], &[]),
+    ];
+
+    assert_states("every_cuepoint", &expected);
+}
+
+#[test]
+fn frame_durations() {
+    let data = fixture("every_cuepoint");
+    let times = data.cuepoints.iter().map(|c| c.0 .0).collect::<Vec<_>>();
+    let (frames, truncated) = Interpreter::new(data).render(MemorySink::default());
+    assert_eq!(truncated, None);
+    assert_eq!(frames.len(), times.len());
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.start, times[i]);
+        // until the next cuepoint, the last one is held for a second
+        let end = times.get(i + 1).copied().unwrap_or(times[i] + 1.0);
+        assert_eq!(frame.duration, end - times[i]);
+    }
+    // back to back, the video's length
+    let total = frames.iter().map(|f| f.duration).sum::<f64>();
+    assert_eq!(total, times.last().unwrap() + 1.0);
+}
+
+#[test]
+fn seeking() {
+    let mut intrp = Interpreter::new(fixture("every_cuepoint"));
+    let at = |intrp: &mut Interpreter, t| {
+        let Snapshot { game_index, move_id, board } = intrp.state_at(t).unwrap();
+        ([game_index, move_id], board.fen())
+    };
+    // forwards, backwards past checkpoints, and right on cuepoints
+    assert_eq!(at(&mut intrp, 16.5), ([1, 1], PROMOTED.to_owned()));
+    assert_eq!(at(&mut intrp, 6.0), ([0, 5], SICILIAN.to_owned()));
+    assert_eq!(at(&mut intrp, 0.0), ([0, 0], START.to_owned()));
+    assert_eq!(at(&mut intrp, 100.0), ([1, 2], KD7.to_owned()));
+    assert!(intrp.state_at(-1.0).is_none());
+}
+
+#[test]
+fn unsorted_cuepoints() {
+    // played in time order, wherever they are in the file
+    let mut intrp = Interpreter::new(fixture("unsorted"));
+    let moves = [0.5, 1.5, 2.5].map(|t| intrp.state_at(t).unwrap().move_id);
+    assert_eq!(moves, [0, 1, 2]);
+    let (snapshots, truncated) = intrp.snapshots();
+    assert_eq!(truncated, None);
+    let frames = snapshots
+        .iter()
+        .map(|(t, d_t, s)| (*t, *d_t, s.move_id))
+        .collect::<Vec<_>>();
+    assert_eq!(frames, [(0.0, 1.0, 0), (1.0, 1.0, 1), (2.0, 1.0, 2)]);
+}
+
+#[test]
+fn missing_game_truncates() {
+    let (snapshots, truncated) = Interpreter::new(fixture("missing_game")).snapshots();
+    assert_eq!(truncated, Some(3.0));
+    let keys = snapshots
+        .iter()
+        .map(|(t, _, s)| (*t, [s.game_index, s.move_id]))
+        .collect::<Vec<_>>();
+    assert_eq!(keys, [(0.0, [0, 0]), (1.5, [0, 1])]);
+}
+
+#[test]
+fn broken_moves() {
+    let (snapshots, truncated) = Interpreter::new(fixture("broken_moves")).snapshots();
+    // a game that isn't there ends the video like any other corrupted cuepoint
+    assert_eq!(truncated, Some(3.0));
+    let keys = snapshots
+        .iter()
+        .map(|(t, _, s)| (*t, [s.game_index, s.move_id]))
+        .collect::<Vec<_>>();
+    assert_eq!(keys, [(0.0, [0, 0]), (1.0, [0, 1]), (2.0, [0, 2])]);
+    // moves whose `pm`s go round in circles are played once each, from where the game was left
+    let (_, _, s) = &snapshots[2];
+    assert_eq!(
+        s.board.fen(),
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq e6 0 2"
+    );
+    assert_eq!(
+        s.board
+            .last_move()
+            .map(|[from, to]| square(from) + &square(to)),
+        Some("e7e5".into())
+    );
+}
+
+#[test]
+fn selecting_a_move() {
+    // the game's starting position is the one at the selected move, which isn't played again
+    #[rustfmt::skip]
+    let expected: [Expected; 2] = [
+        (0.0, [0, 0], E4, Some("e2e4"), &[], &[]),
+        (1.0, [0, 1], E5, Some("e7e5"), &[], &[]),
+    ];
+
+    assert_states("selected_move", &expected);
+}
+
+#[test]
+fn off_line_moves() {
+    // a move none of whose line was shown is played on the board the game was left at
+    #[rustfmt::skip]
+    let expected: [Expected; 3] = [
+        (0.0, [0, 0], START, None, &[], &[]),
+        (1.0, [0, 1], E4, Some("e2e4"), &[], &[]),
+        (2.0, [0, 2], "rnbqkbnr/pppppppp/8/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq d3 0 1", Some("d2d4"), &[], &[]),
+    ];
+
+    assert_states("off_line", &expected);
+}
+
+#[test]
+fn nothing_shown_yet() {
+    let data = fixture("unshown");
+    // frames before any game was shown are an empty board, and promoting from an empty square
+    // leaves it at that
+    let (frames, truncated) = Interpreter::new(data.clone()).render(MemorySink::default());
+    assert_eq!(truncated, None);
+    assert_eq!(frames.len(), 4);
+    assert!(frames[0].image == Chessboard::new().render());
+    let (snapshots, truncated) = Interpreter::new(data).snapshots();
+    assert_eq!(truncated, None);
+    let (t, _, s) = &snapshots[snapshots.len() - 1];
+    assert_eq!(*t, 3.0);
+    assert_eq!(
+        s.board.fen(),
+        "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1"
+    );
+}
+
+#[test]
+fn selecting_unvisited_games() {
+    #[rustfmt::skip]
+    let expected: [Expected; 6] = [
+        // the start of a game never shown, through its root FEN
+        (0.0, [0, 0], START, None, &[], &[]),
+        (1.0, [0, 1], E4, Some("e2e4"), &[], &[]),
+        // or its first move when it doesn't have one
+        (2.0, [1, 0], PROMOTED, Some("a7a8"), &[], &[]),
+        (3.0, [0, 1], E4, Some("e2e4"), &[], &[]),
+        // or just its starting position when it has no moves at all
+        (4.0, [2, 0], "8/8/8/4k3/8/4K3/8/8 w - - 0 1", None, &[], &[]),
+        (5.0, [1, 0], PROMOTED, Some("a7a8"), &[], &[]),
+    ];
+
+    assert_states("unvisited_games", &expected);
+}
+
+#[test]
+fn markers_stay_with_their_game() {
+    #[rustfmt::skip]
+    let expected: [Expected; 8] = [
+        (0.0, [0, 0], START, None, &[], &[]),
+        (1.0, [0, 1], E4, Some("e2e4"), &[], &[]),
+        (2.0, [1, 0], ENDGAME, None, &[], &[]),
+        // markers for the game that isn't shown don't end up on the one that is
+        (3.0, [1, 0], ENDGAME, None, &[], &[]),
+        (4.0, [1, 0], ENDGAME, None, &["e1e2"], &[]),
+        // but are there once it's back
+        (5.0, [0, 1], E4, Some("e2e4"), &[], &["e4"]),
+        (6.0, [0, 1], E4, Some("e2e4"), &[], &["e4"]),
+        (7.0, [1, 0], ENDGAME, None, &[], &[]),
+    ];
+    assert_states("two_games", &expected);
+}
+
+#[test]
+fn two_boards() {
+    let (snapshots, _) = Interpreter::new(fixture("two_games")).snapshots();
+    let (frames, _) = Interpreter::new(fixture("two_games"))
+        .two_boards(true)
+        .render(MemorySink::default());
+    let board = |i: usize| &snapshots[i].2.board;
+    // a single board until there's a second game, then both with the first one on the left
+    assert!(frames[1].image == board(1).render());
+    assert!(frames[2].image == Chessboard::render_pair(board(1), board(2)));
+    assert!(frames[5].image == Chessboard::render_pair(board(5), board(4)));
+}
+
+#[test]
+fn annotations() {
+    let (snapshots, truncated) = Interpreter::new(fixture("annotations")).snapshots();
+    assert_eq!(truncated, None);
+    let marks = |board: &Chessboard| {
+        let squares = |marks: &mut dyn Iterator<Item = [u32; 2]>| {
+            let mut marks = marks.map(square).collect::<Vec<_>>();
+            marks.sort_unstable();
+            marks
+        };
+        let mut glyphs = board
+            .glyphs()
+            .map(|(sq, g)| square(sq) + g.as_str())
+            .collect::<Vec<_>>();
+        glyphs.sort_unstable();
+        [
+            squares(&mut board.circles().map(|(sq, _)| sq)),
+            squares(&mut board.fills().map(|(sq, _)| sq)),
+            glyphs,
+        ]
+    };
+    #[rustfmt::skip]
+    // circles, filled squares, glyphs
+    let expected: [[&[&str]; 3]; 11] = [
+        [&[], &[], &[]],
+        // the move's own glyph, on the square it went to
+        [&[], &[], &["e4!"]],
+        [&["d4"], &[], &["e4!"]],
+        [&["d4"], &["e5"], &["e4!"]],
+        [&["d4"], &["e5"], &["d4?!", "e4!"]],
+        // unmark takes everything off the square
+        [&[], &["e5"], &["e4!"]],
+        // clearAllHighlights leaves the glyphs
+        [&[], &[], &["e4!"]],
+        // and the next move clears them, bringing its own
+        [&[], &[], &["e5?"]],
+        // NAGs other than the six are left out
+        [&[], &[], &[]],
+        [&[], &[], &["f3!!"]],
+        [&[], &[], &[]],
+    ];
+    assert_eq!(snapshots.len(), expected.len());
+    for ((t, _, s), expected) in snapshots.iter().zip(expected) {
+        for (marks, expected) in marks(&s.board).iter().zip(expected) {
+            assert_eq!(marks, &expected, "at {t}");
+        }
+    }
+    let game = &fixture("annotations").games[0];
+    assert_eq!(game.san(1).as_deref(), Some("1. e4!"));
+    assert_eq!(game.san(3).as_deref(), Some("2. Nf3"));
+}
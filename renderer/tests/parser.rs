@@ -0,0 +1,194 @@
+// property tests for everything that reads chess24's data, whatever comes in has to end up as an
+// error rather than a panic or a square off the board; `fuzz/` has the same targets for cargo-fuzz
+use proptest::{collection, prelude::*};
+use renderer::{
+    instr::{line_as_can2coord, Fen},
+    Chessboard, DataFile,
+};
+use serde_json::{Map, Value};
+
+const PIECES: &str = "PNBRQKpnbrqk";
+
+// the datafile's own names turn up as keys and strings way more often than chance would have
+// them, so that generated files get past the first few checks
+#[rustfmt::skip]
+const WORDS: &[&str] = &[
+    "cuepoints", "games", "metadata", "title", "name", "time", "data", "gameIndex", "id", "pm",
+    "m", "fen", "moves", "video_start_fen", "initialMoveId", "x", "y", "color", "lineAsCan", "move",
+    "gotoId", "selectGame", "highlightSquare", "drawArrow", "unmark", "unmarkAll",
+    "clearAllHighlights", "unarrow", "unarrowAll", "triggerExerciseGroup", "red", "green", "blue",
+    "yellow", "drawCircle", "fillSquare", "drawGlyph", "glyph", "nag", "!", "?!", "$3", "e2e4",
+    "e7e8q", "a1h8", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+];
+
+fn word() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => prop::sample::select(WORDS).prop_map(str::to_owned),
+        1 => ".{0,12}",
+    ]
+}
+
+fn json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        (-2i64..80).prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        word().prop_map(Value::from),
+    ];
+    leaf.prop_recursive(5, 96, 8, |inner| {
+        prop_oneof![
+            collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+            collection::vec((word(), inner), 0..8)
+                .prop_map(|kv| Value::Object(kv.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+// somewhat shaped like a datafile, with anything at all in the leaves
+fn datafile() -> impl Strategy<Value = Value> {
+    let cuepoint = (word(), json(), json()).prop_map(
+        |(name, time, data)| serde_json::json!({ "name": name, "time": time, "data": data }),
+    );
+    let mov = (json(), json(), json())
+        .prop_map(|(id, pm, m)| serde_json::json!({ "id": id, "pm": pm, "m": m }));
+    let game = (json(), collection::vec(mov, 0..8))
+        .prop_map(|(fen, moves)| serde_json::json!({ "video_start_fen": fen, "moves": moves }));
+    (collection::vec(cuepoint, 0..8), collection::vec(game, 0..3)).prop_map(
+        |(cuepoints, games)| serde_json::json!({ "cuepoints": cuepoints, "games": games }),
+    )
+}
+
+fn placement() -> impl Strategy<Value = String> {
+    let square = prop::option::weighted(0.4, prop::sample::select(PIECES.as_bytes()));
+    collection::vec(collection::vec(square, 8), 8).prop_map(|ranks| {
+        let ranks = ranks.into_iter().map(|rank| {
+            let mut out = String::new();
+            let mut empty = 0;
+            for sq in rank {
+                match sq {
+                    Some(p) => {
+                        if empty > 0 {
+                            out.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        out.push(char::from(p));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                out.push_str(&empty.to_string());
+            }
+            out
+        });
+        ranks.collect::<Vec<_>>().join("/")
+    })
+}
+
+fn on_board([f, r]: [u32; 2]) -> bool { (1..=8).contains(&f) && (1..=8).contains(&r) }
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn datafile_from_any_json(value in json()) {
+        let _ = serde_json::from_value::<DataFile>(value);
+    }
+
+    #[test]
+    fn datafile_from_any_shaped_json(value in datafile()) {
+        let _ = serde_json::from_value::<DataFile>(value);
+    }
+
+    #[test]
+    fn datafile_from_any_bytes(bytes in collection::vec(any::<u8>(), 0..256)) {
+        let _ = serde_json::from_slice::<DataFile>(&bytes);
+    }
+
+    #[test]
+    fn fen_from_anything(fen in prop_oneof![".{0,96}", "[pnbrqkPNBRQK0-9/ \\\\wb-]{0,96}"]) {
+        let squares = Fen::new(fen.clone()).iter().collect::<Vec<_>>();
+        prop_assert!(squares.len() <= 64);
+        prop_assert!(squares.iter().all(|(sq, _)| on_board(*sq)));
+        if let Ok(fen) = Fen::parse(&fen) {
+            prop_assert_eq!(fen.iter().count(), 64);
+            Chessboard::new().set_fen(&fen);
+        }
+    }
+
+    #[test]
+    fn fen_round_trip(placement in placement(), turn in "[wb]") {
+        let fen = Fen::parse(&format!("{placement} {turn} - - 0 1")).unwrap();
+        let mut board = Chessboard::new();
+        board.set_fen(&fen);
+        prop_assert_eq!(board.fen(), format!("{placement} {turn} - - 0 1"));
+    }
+
+    #[test]
+    fn line_as_can_from_anything(line in prop_oneof![".{0,8}", "[a-j0-9qrbnk]{0,6}"]) {
+        if let Some((squares, promotion)) = line_as_can2coord(&line) {
+            prop_assert!(squares.iter().all(|sq| on_board(*sq)));
+            prop_assert!(promotion.is_none_or(|p| p < 6));
+        }
+    }
+
+    #[test]
+    fn line_as_can_of_moves(line in "[a-h][1-8][a-h][1-8][qrbn]?") {
+        let (squares, promotion) = line_as_can2coord(&line).unwrap();
+        let b = line.as_bytes();
+        prop_assert_eq!(squares, [
+            [u32::from(b[0] - b'a' + 1), u32::from(b[1] - b'0')],
+            [u32::from(b[2] - b'a' + 1), u32::from(b[3] - b'0')],
+        ]);
+        prop_assert_eq!(promotion.is_some(), line.len() == 5);
+    }
+}
+
+#[test]
+fn malformed_cuepoints() {
+    let datafile = |cuepoint: Value| {
+        serde_json::from_value::<DataFile>(
+            serde_json::json!({ "cuepoints": [cuepoint], "games": [] }),
+        )
+    };
+    for (name, data) in [
+        ("teleport", serde_json::json!({ "gameIndex": 0 })),
+        (
+            "highlightSquare",
+            serde_json::json!({ "gameIndex": 0, "x": 8, "y": 0, "color": "red" }),
+        ),
+        (
+            "highlightSquare",
+            serde_json::json!({ "gameIndex": 0, "x": 0, "y": 0, "color": "pink" }),
+        ),
+        (
+            "drawArrow",
+            serde_json::json!({ "gameIndex": 0, "lineAsCan": "e2", "color": "red" }),
+        ),
+        (
+            "unarrow",
+            serde_json::json!({ "gameIndex": 0, "lineAsCan": "i2e4" }),
+        ),
+        (
+            "move",
+            serde_json::json!({ "gameIndex": 0, "id": 1, "move": 1, "fen": "8/8 w" }),
+        ),
+        (
+            "drawCircle",
+            serde_json::json!({ "gameIndex": 0, "x": 0, "y": 9, "color": "red" }),
+        ),
+        (
+            "drawGlyph",
+            serde_json::json!({ "gameIndex": 0, "x": 0, "y": 0, "glyph": "!!!" }),
+        ),
+        (
+            "drawGlyph",
+            serde_json::json!({ "gameIndex": 0, "x": 0, "y": 0, "nag": 7 }),
+        ),
+    ] {
+        let err = datafile(serde_json::json!({ "name": name, "time": 0, "data": data }));
+        assert!(err.is_err(), "{name} {data}");
+    }
+}